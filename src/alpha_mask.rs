@@ -0,0 +1,81 @@
+use std::rc::Rc;
+
+use crate::{
+    hittable::{HitRecord, Hittable},
+    ray::Ray,
+    texture::Texture,
+    utility,
+};
+
+// gives up looking for an opaque hit after this many see-through ones
+const MAX_SKIPPED_HITS: i32 = 32;
+
+pub enum AlphaMode {
+    // hits where the opacity falls below the cutoff are skipped entirely
+    Threshold(f64),
+    // hits are skipped with a probability of one minus the opacity, so soft edges average out
+    Stochastic,
+}
+
+// Wraps any object with an opacity texture, so rays pass straight through the transparent
+// parts of it, like the gaps between leaves on a quad of foliage.
+pub struct AlphaMasked<H: Hittable> {
+    object: H,
+    opacity: Rc<dyn Texture>,
+    mode: AlphaMode,
+}
+
+#[allow(dead_code)]
+impl<H: Hittable> AlphaMasked<H> {
+    pub fn new(object: H, opacity: Rc<dyn Texture>, mode: AlphaMode) -> AlphaMasked<H> {
+        AlphaMasked {
+            object,
+            opacity,
+            mode,
+        }
+    }
+    pub fn threshold(object: H, opacity: Rc<dyn Texture>, cutoff: f64) -> AlphaMasked<H> {
+        AlphaMasked::new(object, opacity, AlphaMode::Threshold(cutoff))
+    }
+    pub fn stochastic(object: H, opacity: Rc<dyn Texture>) -> AlphaMasked<H> {
+        AlphaMasked::new(object, opacity, AlphaMode::Stochastic)
+    }
+
+    fn is_opaque(&self, r: &Ray, rec: &HitRecord) -> bool {
+        let alpha = self.opacity.value(rec.u, rec.v, &rec.point).luminance();
+        match self.mode {
+            AlphaMode::Threshold(cutoff) => alpha >= cutoff,
+            AlphaMode::Stochastic => {
+                // hashing the ray keeps the choice consistent if the same ray is traced again
+                let origin = r.origin();
+                let direction = r.direction();
+                let u = utility::hash_to_unit(&[
+                    origin.x(),
+                    origin.y(),
+                    origin.z(),
+                    direction.x(),
+                    direction.y(),
+                    direction.z(),
+                    rec.t,
+                ]);
+                u < alpha
+            }
+        }
+    }
+}
+
+impl<H: Hittable> Hittable for AlphaMasked<H> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        // keep looking further along the ray past every transparent hit, which for a sphere
+        // means trying the far side once the near side is cut out
+        let mut t_min = t_min;
+        for _ in 0..MAX_SKIPPED_HITS {
+            let rec = self.object.hit(r, t_min, t_max)?;
+            if self.is_opaque(r, &rec) {
+                return Some(rec);
+            }
+            t_min = rec.t + 1e-6;
+        }
+        None
+    }
+}
@@ -0,0 +1,396 @@
+use std::fmt;
+use std::io::Result;
+use std::str::FromStr;
+
+use crate::{
+    camera::CameraPose, hittable_vec::HittableVec, image::invalid_data, point::Point,
+    transform::Transformed, vector3::Vector3,
+};
+
+// How a track moves between its keyframes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interpolation {
+    // straight from one key to the next, turning sharply at each
+    Linear,
+    // a smooth curve through every key
+    CatmullRom,
+}
+
+impl FromStr for Interpolation {
+    type Err = String;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(Interpolation::Linear),
+            "catmull-rom" => Ok(Interpolation::CatmullRom),
+            _ => Err(format!("unknown interpolation {s}")),
+        }
+    }
+}
+
+impl fmt::Display for Interpolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Interpolation::Linear => "linear",
+            Interpolation::CatmullRom => "catmull-rom",
+        };
+        write!(f, "{name}")
+    }
+}
+
+// The frames to render, counted from 1 and including both ends.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameRange {
+    pub first: u32,
+    pub last: u32,
+}
+
+impl FromStr for FrameRange {
+    type Err = String;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (first, last) = s.split_once('-').unwrap_or((s, s));
+        match (first.parse(), last.parse()) {
+            (Ok(first), Ok(last)) if 1 <= first && first <= last => Ok(FrameRange { first, last }),
+            _ => Err(format!("invalid frame range {s}")),
+        }
+    }
+}
+
+impl fmt::Display for FrameRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.first, self.last)
+    }
+}
+
+// What a track animates. Objects are numbered from 1 in the order the scene adds them, the
+// same numbers the object-id aov shows.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Property {
+    LookFrom,
+    LookAt,
+    Fov,
+    Focus,
+    Translate(usize),
+    Rotate(usize),
+    Scale(usize),
+    Pivot(usize),
+}
+
+impl Property {
+    // how many numbers each key holds
+    fn size(&self) -> usize {
+        match self {
+            Property::LookFrom | Property::LookAt | Property::Translate(_) | Property::Pivot(_) => {
+                3
+            }
+            Property::Fov | Property::Focus | Property::Rotate(_) | Property::Scale(_) => 1,
+        }
+    }
+    fn object(&self) -> Option<usize> {
+        match self {
+            Property::Translate(id)
+            | Property::Rotate(id)
+            | Property::Scale(id)
+            | Property::Pivot(id) => Some(*id),
+            _ => None,
+        }
+    }
+}
+
+// The keyframes of one property, sorted by frame.
+struct Track {
+    keys: Vec<(f64, Vec<f64>)>,
+}
+
+impl Track {
+    fn value(&self, frame: f64, interpolation: Interpolation) -> Vec<f64> {
+        let keys = &self.keys;
+        let last = keys.len() - 1;
+        if frame <= keys[0].0 {
+            return keys[0].1.clone();
+        }
+        if frame >= keys[last].0 {
+            return keys[last].1.clone();
+        }
+        let i = keys.iter().rposition(|(key, _)| *key <= frame).unwrap_or(0);
+        let ((t0, p0), (t1, p1)) = (&keys[i], &keys[i + 1]);
+        let h = t1 - t0;
+        let s = (frame - t0) / h;
+        match interpolation {
+            Interpolation::Linear => p0.iter().zip(p1).map(|(a, b)| a + (b - a) * s).collect(),
+            Interpolation::CatmullRom => {
+                // a Hermite curve through each pair of keys, leaving each key in the direction
+                // from the key before it to the one after, which copes with uneven spacing
+                let tangent = |k: usize, c: usize| {
+                    let (before, after) = (k.saturating_sub(1), (k + 1).min(last));
+                    (keys[after].1[c] - keys[before].1[c]) / (keys[after].0 - keys[before].0)
+                };
+                let (s2, s3) = (s * s, s * s * s);
+                (0..p0.len())
+                    .map(|c| {
+                        (2.0 * s3 - 3.0 * s2 + 1.0) * p0[c]
+                            + (s3 - 2.0 * s2 + s) * h * tangent(i, c)
+                            + (-2.0 * s3 + 3.0 * s2) * p1[c]
+                            + (s3 - s2) * h * tangent(i + 1, c)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+// Keyframed changes to the camera and the objects of the scene, read from a file with one
+// key per line:
+//
+//   interpolation catmull-rom
+//   lookfrom 1 13 2 3
+//   lookfrom 48 3 2 13
+//   object 5 translate 1 0 0 0
+//   object 5 translate 48 0 3 0
+//
+// Each key names what it animates, then the frame, then the value. The camera has lookfrom
+// and lookat points, fov in degrees and focus distance. Objects are moved with translate,
+// turned about the vertical axis in degrees with rotate and scaled with scale, the last two
+// around their pivot point, which is the world origin unless given. Anything without keys
+// stays as the scene built it. All tracks use the same interpolation, linear
+// unless the file says otherwise.
+pub struct Animation {
+    interpolation: Interpolation,
+    tracks: Vec<(Property, Track)>,
+}
+
+#[allow(dead_code)]
+impl Animation {
+    pub fn load(file_name: &str) -> Result<Animation> {
+        let text = std::fs::read_to_string(file_name)?;
+        let mut interpolation = Interpolation::Linear;
+        let mut tracks: Vec<(Property, Track)> = Vec::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || invalid_data(&format!("invalid keyframe {line}"));
+            let mut tokens = line.split_whitespace();
+            let property = match tokens.next() {
+                Some("interpolation") => {
+                    let name = tokens.next().ok_or_else(invalid)?;
+                    interpolation = name.parse().map_err(|e: String| invalid_data(&e))?;
+                    continue;
+                }
+                Some("lookfrom") => Property::LookFrom,
+                Some("lookat") => Property::LookAt,
+                Some("fov") => Property::Fov,
+                Some("focus") => Property::Focus,
+                Some("object") => {
+                    let id: usize = tokens
+                        .next()
+                        .and_then(|id| id.parse().ok())
+                        .filter(|id| *id > 0)
+                        .ok_or_else(invalid)?;
+                    match tokens.next() {
+                        Some("translate") => Property::Translate(id),
+                        Some("rotate") => Property::Rotate(id),
+                        Some("scale") => Property::Scale(id),
+                        Some("pivot") => Property::Pivot(id),
+                        _ => return Err(invalid()),
+                    }
+                }
+                _ => return Err(invalid()),
+            };
+            let numbers: Vec<f64> = tokens
+                .map(|token| token.parse())
+                .collect::<std::result::Result<_, _>>()
+                .map_err(|_| invalid())?;
+            if numbers.len() != 1 + property.size() {
+                return Err(invalid());
+            }
+            let positive = matches!(
+                property,
+                Property::Fov | Property::Focus | Property::Scale(_)
+            );
+            if positive && numbers[1] <= 0.0 {
+                return Err(invalid_data(&format!("{line} must be greater than 0")));
+            }
+            let key = (numbers[0], numbers[1..].to_vec());
+            match tracks.iter_mut().find(|(p, _)| *p == property) {
+                Some((_, track)) => track.keys.push(key),
+                None => tracks.push((property, Track { keys: vec![key] })),
+            }
+        }
+        if tracks.is_empty() {
+            return Err(invalid_data("the animation has no keyframes"));
+        }
+        for (_, track) in tracks.iter_mut() {
+            track.keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        }
+        Ok(Animation {
+            interpolation,
+            tracks,
+        })
+    }
+
+    // from the first keyframe to the last
+    pub fn frames(&self) -> FrameRange {
+        let keys = self.tracks.iter().flat_map(|(_, track)| &track.keys);
+        let first = keys
+            .clone()
+            .map(|(frame, _)| *frame)
+            .fold(f64::INFINITY, f64::min);
+        let last = keys
+            .map(|(frame, _)| *frame)
+            .fold(f64::NEG_INFINITY, f64::max);
+        FrameRange {
+            first: first.round().max(1.0) as u32,
+            last: last.round().max(1.0) as u32,
+        }
+    }
+
+    // the highest object number any track animates
+    pub fn max_object(&self) -> usize {
+        self.tracks
+            .iter()
+            .filter_map(|(property, _)| property.object())
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn value(&self, property: Property, frame: f64) -> Option<Vec<f64>> {
+        self.tracks
+            .iter()
+            .find(|(p, _)| *p == property)
+            .map(|(_, track)| track.value(frame, self.interpolation))
+    }
+
+    // The camera at a frame, starting from pose for anything not animated.
+    pub fn camera_pose(&self, frame: f64, pose: CameraPose) -> CameraPose {
+        let point = |value: Vec<f64>| Point::new(value[0], value[1], value[2]);
+        CameraPose {
+            lookfrom: self
+                .value(Property::LookFrom, frame)
+                .map_or(pose.lookfrom, point),
+            lookat: self
+                .value(Property::LookAt, frame)
+                .map_or(pose.lookat, point),
+            fov: self
+                .value(Property::Fov, frame)
+                .map(|v| v[0].max(1e-3))
+                .or(pose.fov),
+            // a curve through the keys can swing past them, but never through zero
+            focus_distance: self
+                .value(Property::Focus, frame)
+                .map(|v| v[0].max(1e-3))
+                .or(pose.focus_distance),
+        }
+    }
+
+    // Moves the animated objects of the world to where they are at a frame.
+    pub fn pose_world(&self, frame: f64, world: &mut HittableVec) {
+        let mut objects: Vec<usize> = self
+            .tracks
+            .iter()
+            .filter_map(|(property, _)| property.object())
+            .collect();
+        objects.sort_unstable();
+        objects.dedup();
+        for id in objects {
+            let translation = self
+                .value(Property::Translate(id), frame)
+                .map_or(Vector3::new(0.0, 0.0, 0.0), |v| {
+                    Vector3::new(v[0], v[1], v[2])
+                });
+            let rotation = self
+                .value(Property::Rotate(id), frame)
+                .map_or(0.0, |v| v[0]);
+            let scale = self
+                .value(Property::Scale(id), frame)
+                .map_or(1.0, |v| v[0].max(1e-6));
+            let pivot = self
+                .value(Property::Pivot(id), frame)
+                .map_or(Point::origin(), |v| Point::new(v[0], v[1], v[2]));
+            world.wrap(id - 1, |object| {
+                Box::new(Transformed::new(object, translation, rotation, scale).with_pivot(pivot))
+            });
+        }
+    }
+}
+
+// The file a frame is written to, numbered before the extension like frame_0001.ppm.
+pub fn frame_file_name(file_name: &str, frame: u32) -> String {
+    let name_start = file_name.rfind('/').map_or(0, |i| i + 1);
+    match file_name[name_start..].rfind('.') {
+        Some(dot) => {
+            let (stem, extension) = file_name.split_at(name_start + dot);
+            format!("{stem}_{frame:04}{extension}")
+        }
+        None => format!("{file_name}_{frame:04}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(keys: &[(f64, f64)]) -> Track {
+        Track {
+            keys: keys
+                .iter()
+                .map(|&(frame, value)| (frame, vec![value]))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn linear_goes_straight_between_keys() {
+        let track = track(&[(1.0, 0.0), (3.0, 4.0), (4.0, 2.0)]);
+        let value = |frame| track.value(frame, Interpolation::Linear)[0];
+        assert_eq!(value(1.0), 0.0);
+        assert_eq!(value(3.0), 4.0);
+        assert_eq!(value(2.0), 2.0);
+        assert_eq!(value(3.5), 3.0);
+        // before the first key and after the last it holds still
+        assert_eq!(value(-5.0), 0.0);
+        assert_eq!(value(10.0), 2.0);
+    }
+
+    #[test]
+    fn catmull_rom_passes_through_every_key() {
+        let track = track(&[(0.0, 0.0), (1.0, 1.0), (2.0, 0.0), (5.0, 3.0)]);
+        for (frame, key) in [(0.0, 0.0), (1.0, 1.0), (2.0, 0.0), (5.0, 3.0)] {
+            let value = track.value(frame, Interpolation::CatmullRom)[0];
+            assert!((value - key).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn catmull_rom_curves_between_keys() {
+        // evenly spaced keys on a line stay on it
+        let line = track(&[(0.0, 0.0), (1.0, 1.0), (2.0, 2.0), (3.0, 3.0)]);
+        let value = line.value(1.5, Interpolation::CatmullRom)[0];
+        assert!((value - 1.5).abs() < 1e-12);
+        // a peak leaves its first key heading up and arrives at the top flat, so halfway
+        // there it has already passed the straight line
+        let peak = track(&[(0.0, 0.0), (1.0, 1.0), (2.0, 0.0)]);
+        let value = peak.value(0.5, Interpolation::CatmullRom)[0];
+        assert!((value - 0.625).abs() < 1e-12);
+    }
+
+    #[test]
+    fn frame_ranges_parse() {
+        assert_eq!("3-7".parse(), Ok(FrameRange { first: 3, last: 7 }));
+        assert_eq!("5".parse(), Ok(FrameRange { first: 5, last: 5 }));
+        assert!("0-4".parse::<FrameRange>().is_err());
+        assert!("7-3".parse::<FrameRange>().is_err());
+        assert!("a-b".parse::<FrameRange>().is_err());
+    }
+
+    #[test]
+    fn frames_are_numbered_before_the_extension() {
+        assert_eq!(frame_file_name("output.ppm", 1), "output_0001.ppm");
+        assert_eq!(frame_file_name("out/depth.pfm", 42), "out/depth_0042.pfm");
+        assert_eq!(frame_file_name("render", 7), "render_0007");
+        // a dot in a directory name isn't an extension
+        assert_eq!(
+            frame_file_name("shots.v2/render", 12),
+            "shots.v2/render_0012"
+        );
+    }
+}
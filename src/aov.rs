@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Result;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use crate::{
+    color::Color, hittable::HitRecord, image::Image, region::Window, utility, vector3::Vector3,
+};
+
+// Extra images about what the camera sees, for compositing and denoising: what the camera
+// rays hit first, instead of the light arriving along them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AovKind {
+    Albedo,
+    Normal,
+    Depth,
+    Position,
+    ObjectId,
+    MaterialId,
+}
+
+impl FromStr for AovKind {
+    type Err = String;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "albedo" => Ok(AovKind::Albedo),
+            "normal" => Ok(AovKind::Normal),
+            "depth" => Ok(AovKind::Depth),
+            "position" => Ok(AovKind::Position),
+            "object-id" => Ok(AovKind::ObjectId),
+            "material-id" => Ok(AovKind::MaterialId),
+            _ => Err(format!("unknown aov {s}")),
+        }
+    }
+}
+
+impl fmt::Display for AovKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            AovKind::Albedo => "albedo",
+            AovKind::Normal => "normal",
+            AovKind::Depth => "depth",
+            AovKind::Position => "position",
+            AovKind::ObjectId => "object-id",
+            AovKind::MaterialId => "material-id",
+        };
+        write!(f, "{name}")
+    }
+}
+
+// One extra image to write, given on the command line as name=file. A .pfm file gets the
+// raw values, a .ppm file a version that is easy to look at.
+pub struct AovOutput {
+    pub kind: AovKind,
+    pub file_name: String,
+}
+
+impl FromStr for AovOutput {
+    type Err = String;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (kind, file_name) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected name=file for --aov, got {s}"))?;
+        if !file_name.ends_with(".ppm") && !file_name.ends_with(".pfm") {
+            return Err(format!("{file_name} should be a .ppm or .pfm file"));
+        }
+        Ok(AovOutput {
+            kind: kind.parse()?,
+            file_name: file_name.to_string(),
+        })
+    }
+}
+
+// What the camera rays through one pixel hit first. Colors and vectors are summed, ids are
+// tallied so the pixel can show the one most of its rays hit.
+struct AovPixel {
+    samples: u32,
+    hits: u32,
+    albedo: Color,
+    normal: Vector3,
+    position: Vector3,
+    depth: f64,
+    // (id + 1, rays that hit it), where 0 stands for nothing hit
+    object_ids: Vec<(usize, u32)>,
+    material_ids: Vec<(usize, u32)>,
+}
+
+impl AovPixel {
+    fn new() -> AovPixel {
+        AovPixel {
+            samples: 0,
+            hits: 0,
+            albedo: Color::new(0.0, 0.0, 0.0),
+            normal: Vector3::new(0.0, 0.0, 0.0),
+            position: Vector3::new(0.0, 0.0, 0.0),
+            depth: 0.0,
+            object_ids: Vec::new(),
+            material_ids: Vec::new(),
+        }
+    }
+    // the id most of the rays hit, counting from 1 with 0 for nothing
+    fn most_common(tally: &[(usize, u32)]) -> usize {
+        tally
+            .iter()
+            .max_by_key(|(_, count)| *count)
+            .map_or(0, |(id, _)| *id)
+    }
+}
+
+fn tally(ids: &mut Vec<(usize, u32)>, id: usize) {
+    match ids.iter_mut().find(|(seen, _)| *seen == id) {
+        Some((_, count)) => *count += 1,
+        None => ids.push((id, 1)),
+    }
+}
+
+// The extra images, filled in by the renderer as it traces each camera ray and stored row by
+// row from the top left like the film.
+pub struct AovFilm {
+    width: i32,
+    height: i32,
+    pixels: Vec<AovPixel>,
+    // materials numbered in the order the camera rays first meet them
+    material_ids: HashMap<usize, usize>,
+}
+
+impl AovFilm {
+    pub fn new(width: i32, height: i32) -> AovFilm {
+        AovFilm {
+            width,
+            height,
+            pixels: (0..width * height).map(|_| AovPixel::new()).collect(),
+            material_ids: HashMap::new(),
+        }
+    }
+    // how many camera rays through pixel (x, row) have been recorded
+    pub fn samples(&self, x: i32, row: i32) -> u32 {
+        self.pixels[(row * self.width + x) as usize].samples
+    }
+
+    // Records a camera ray through pixel (x, row) that hit rec at the given distance, or
+    // nothing at all.
+    pub fn add_sample(&mut self, x: i32, row: i32, hit: Option<(&HitRecord, f64)>) {
+        let pixel = &mut self.pixels[(row * self.width + x) as usize];
+        pixel.samples += 1;
+        match hit {
+            Some((rec, depth)) => {
+                let key = Rc::as_ptr(&rec.material) as *const u8 as usize;
+                let next_id = self.material_ids.len();
+                let material_id = *self.material_ids.entry(key).or_insert(next_id);
+                pixel.hits += 1;
+                pixel.albedo += rec.material.albedo(rec);
+                pixel.normal += rec.normal.clone();
+                pixel.position += Vector3::new(rec.point.x(), rec.point.y(), rec.point.z());
+                pixel.depth += depth;
+                tally(&mut pixel.object_ids, rec.object_id + 1);
+                tally(&mut pixel.material_ids, material_id + 1);
+            }
+            None => {
+                tally(&mut pixel.object_ids, 0);
+                tally(&mut pixel.material_ids, 0);
+            }
+        }
+    }
+
+    // The raw values of an output. Rays that hit nothing count as black for albedo and
+    // normal, while depth and position average only the rays that hit something and are 0
+    // where none did. Ids count from 1, with 0 where nothing was hit.
+    pub fn image(&self, kind: AovKind) -> Image {
+        let pixels = self
+            .pixels
+            .iter()
+            .map(|pixel| {
+                let per_sample = 1.0 / pixel.samples.max(1) as f64;
+                let per_hit = 1.0 / pixel.hits.max(1) as f64;
+                match kind {
+                    AovKind::Albedo => pixel.albedo.clone() * per_sample,
+                    AovKind::Normal => Color::from_vector(pixel.normal.clone() * per_sample),
+                    AovKind::Depth => {
+                        let depth = pixel.depth * per_hit;
+                        Color::new(depth, depth, depth)
+                    }
+                    AovKind::Position => Color::from_vector(pixel.position.clone() * per_hit),
+                    AovKind::ObjectId => {
+                        let id = AovPixel::most_common(&pixel.object_ids) as f64;
+                        Color::new(id, id, id)
+                    }
+                    AovKind::MaterialId => {
+                        let id = AovPixel::most_common(&pixel.material_ids) as f64;
+                        Color::new(id, id, id)
+                    }
+                }
+            })
+            .collect();
+        Image::new(self.width as usize, self.height as usize, pixels)
+    }
+
+    // A version of an output that makes sense as an ordinary picture: albedo with the usual
+    // gamma, normals mapped from -1..1 to 0..1, depth from white up close to black far away,
+    // positions scaled to the box around everything hit, and a made up color for each id.
+    fn preview(&self, kind: AovKind) -> Image {
+        let values = self.image(kind);
+        let hit = |x: usize, y: usize| self.pixels[y * self.width as usize + x].hits > 0;
+        let mut low = [f64::INFINITY; 3];
+        let mut high = [f64::NEG_INFINITY; 3];
+        for y in 0..values.height() {
+            for x in 0..values.width() {
+                if hit(x, y) {
+                    let c = values.pixel(x, y);
+                    for (i, value) in [c.red(), c.green(), c.blue()].into_iter().enumerate() {
+                        low[i] = low[i].min(value);
+                        high[i] = high[i].max(value);
+                    }
+                }
+            }
+        }
+        let scale = |value: f64, i: usize| (value - low[i]) / (high[i] - low[i]).max(1e-9);
+
+        let mut pixels = Vec::with_capacity(values.width() * values.height());
+        for y in 0..values.height() {
+            for x in 0..values.width() {
+                let c = values.pixel(x, y);
+                pixels.push(match kind {
+                    AovKind::Albedo => {
+                        Color::new(c.red().sqrt(), c.green().sqrt(), c.blue().sqrt())
+                    }
+                    AovKind::Normal => Color::new(
+                        0.5 * c.red() + 0.5,
+                        0.5 * c.green() + 0.5,
+                        0.5 * c.blue() + 0.5,
+                    ),
+                    _ if !hit(x, y) => Color::new(0.0, 0.0, 0.0),
+                    AovKind::Depth => {
+                        let level = 1.0 - 0.9 * scale(c.red(), 0);
+                        Color::new(level, level, level)
+                    }
+                    AovKind::Position => {
+                        Color::new(scale(c.red(), 0), scale(c.green(), 1), scale(c.blue(), 2))
+                    }
+                    AovKind::ObjectId | AovKind::MaterialId => id_color(c.red() as u64),
+                });
+            }
+        }
+        Image::new(values.width(), values.height(), pixels)
+    }
+
+    // Writes one of the images, cut down to the window if given.
+    pub fn write(&self, output: &AovOutput, crop: Option<&Window>) -> Result<()> {
+        let pfm = output.file_name.ends_with(".pfm");
+        let image = if pfm {
+            self.image(output.kind)
+        } else {
+            self.preview(output.kind)
+        };
+        let image = match crop {
+            Some(window) => image.crop(window),
+            None => image,
+        };
+        if pfm {
+            image.write_pfm(&output.file_name)
+        } else {
+            image.write_ppm(&output.file_name)
+        }
+    }
+}
+
+// a bright color that is the same every time for the same id
+fn id_color(id: u64) -> Color {
+    let bits = utility::hash(&[id]);
+    let channel = |shift: u64| 0.25 + 0.75 * ((bits >> shift) & 0xff) as f64 / 255.0;
+    Color::new(channel(0), channel(8), channel(16))
+}
@@ -0,0 +1,92 @@
+use std::f64::consts::PI;
+
+use crate::{
+    distribution::Distribution2D,
+    image::{invalid_data, Image},
+    vector3::Vector3,
+};
+
+// The opening of a lens that light passes through, which gives out of focus highlights
+// their shape. Points are sampled across it in lens units, where the aperture radius is 1.
+pub trait Aperture {
+    fn sample(&self, u: (f64, f64)) -> (f64, f64);
+}
+
+// A perfectly round opening, as the camera has always had.
+pub struct CircularAperture;
+
+impl Aperture for CircularAperture {
+    fn sample(&self, u: (f64, f64)) -> (f64, f64) {
+        let p = Vector3::sample_in_unit_disk(u);
+        (p.x(), p.y())
+    }
+}
+
+// The regular polygon left open by a diaphragm of straight blades, with a corner pointing
+// straight up before it's rotated.
+pub struct PolygonAperture {
+    corners: Vec<(f64, f64)>,
+}
+
+#[allow(dead_code)]
+impl PolygonAperture {
+    // rotation is in degrees, counterclockwise
+    pub fn new(blades: u32, rotation: f64) -> PolygonAperture {
+        let corners = (0..blades)
+            .map(|i| {
+                let angle = PI / 2.0 + rotation.to_radians() + 2.0 * PI * i as f64 / blades as f64;
+                (angle.cos(), angle.sin())
+            })
+            .collect();
+        PolygonAperture { corners }
+    }
+}
+
+impl Aperture for PolygonAperture {
+    fn sample(&self, (u1, u2): (f64, f64)) -> (f64, f64) {
+        // pick one of the triangles between the center and two neighboring corners, reusing
+        // what's left of u1 to place the point inside it evenly
+        let count = self.corners.len();
+        let scaled = u1 * count as f64;
+        let i = (scaled as usize).min(count - 1);
+        let along_radius = (scaled - i as f64).sqrt();
+        let (a, b) = (self.corners[i], self.corners[(i + 1) % count]);
+        (
+            along_radius * (a.0 + u2 * (b.0 - a.0)),
+            along_radius * (a.1 + u2 * (b.1 - a.1)),
+        )
+    }
+}
+
+// An opening cut in the shape of an image, like the star or heart shaped masks put in front
+// of a lens for special effects. The image is stretched over the square around the unit
+// circle and light passes where it's bright, more where it's brighter.
+pub struct ImageAperture {
+    distribution: Distribution2D,
+}
+
+#[allow(dead_code)]
+impl ImageAperture {
+    pub fn load(file_name: &str) -> std::io::Result<ImageAperture> {
+        let image = Image::load(file_name)?;
+        let brightness: Vec<f64> = image
+            .pixels()
+            .iter()
+            .map(|color| color.luminance().max(0.0))
+            .collect();
+        if brightness.iter().all(|&b| b == 0.0) {
+            return Err(invalid_data("the aperture image is black everywhere"));
+        }
+        Ok(ImageAperture {
+            distribution: Distribution2D::new(&brightness, image.width(), image.height()),
+        })
+    }
+}
+
+impl Aperture for ImageAperture {
+    fn sample(&self, (u1, u2): (f64, f64)) -> (f64, f64) {
+        let ((x, y), _pdf) = self.distribution.sample_continuous(u1, u2);
+        // the rows of the image run from the top down
+        (2.0 * x - 1.0, 1.0 - 2.0 * y)
+    }
+}
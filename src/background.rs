@@ -0,0 +1,34 @@
+use crate::{color::Color, sampler::Sampler, vector3::Vector3};
+
+// What a ray sees when it escapes the scene without hitting anything. Backgrounds that can
+// pick directions toward their bright parts also light the scene through direct sampling.
+pub trait Background {
+    fn value(&self, direction: &Vector3) -> Color;
+
+    // A direction toward the background, the radiance arriving from it and the pdf of
+    // choosing it (per unit solid angle). None when the background can't be sampled.
+    fn sample(&self, _sampler: &mut dyn Sampler) -> Option<(Vector3, Color, f64)> {
+        None
+    }
+    fn pdf(&self, _direction: &Vector3) -> f64 {
+        0.0
+    }
+}
+
+// The plain white to blue sky the renderer has always used.
+pub struct SkyGradient {}
+
+#[allow(dead_code)]
+impl SkyGradient {
+    pub fn new() -> SkyGradient {
+        SkyGradient {}
+    }
+}
+
+impl Background for SkyGradient {
+    fn value(&self, direction: &Vector3) -> Color {
+        let unit_direction = Vector3::unit_vector(direction);
+        let t = 0.5 * (unit_direction.y() + 1.0);
+        Color::new(1.0, 1.0, 1.0) * (1.0 - t) + Color::new(0.5, 0.7, 1.0) * t
+    }
+}
@@ -1,11 +1,209 @@
-use crate::{point::Point, ray::Ray, utility::degrees_to_radians, vector3::Vector3};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{
+    aperture::{Aperture, CircularAperture},
+    point::Point,
+    ray::Ray,
+    sampler::Sampler,
+    utility::degrees_to_radians,
+    vector3::Vector3,
+};
 
 // pub const ASPECT_RATIO: f64 = 16.0 / 9.0;
 // const VIEWPORT_HEIGHT: f64 = 2.0;
 // const VIEWPORT_WIDTH: f64 = ASPECT_RATIO * VIEWPORT_HEIGHT;
 // const FOCAL_LENGTH: f64 = 1.0;
 
-pub struct Camera {
+// Turns a point on the image into the ray that renders it. s and t run from 0 to 1 across
+// the image, left to right and bottom to top. Parts of the image a camera doesn't see, like
+// the corners around a circular fisheye, have no ray and stay black.
+pub trait Camera {
+    fn get_ray(&self, s: f64, t: f64, sampler: &mut dyn Sampler) -> Option<Ray>;
+
+    // The ray along with how much the light it brings back counts, for cameras that don't
+    // pick their rays evenly. Every ray counts the same for most cameras.
+    fn get_weighted_ray(&self, s: f64, t: f64, sampler: &mut dyn Sampler) -> Option<(Ray, f64)> {
+        self.get_ray(s, t, sampler).map(|r| (r, 1.0))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CameraKind {
+    Perspective,
+    Orthographic,
+    Fisheye,
+    Equirectangular,
+    Cubemap,
+}
+
+impl FromStr for CameraKind {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "perspective" => Ok(CameraKind::Perspective),
+            "orthographic" => Ok(CameraKind::Orthographic),
+            "fisheye" => Ok(CameraKind::Fisheye),
+            "equirectangular" => Ok(CameraKind::Equirectangular),
+            "cubemap" => Ok(CameraKind::Cubemap),
+            _ => Err(format!("unknown camera {s}")),
+        }
+    }
+}
+
+impl fmt::Display for CameraKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            CameraKind::Perspective => "perspective",
+            CameraKind::Orthographic => "orthographic",
+            CameraKind::Fisheye => "fisheye",
+            CameraKind::Equirectangular => "equirectangular",
+            CameraKind::Cubemap => "cubemap",
+        };
+        write!(f, "{name}")
+    }
+}
+
+// The size of a camera's sensor in millimeters, which together with the focal length of the
+// lens decides how much of the scene fits in the picture.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sensor {
+    pub width: f64,
+    pub height: f64,
+}
+
+#[allow(dead_code)]
+impl Sensor {
+    pub fn new(width: f64, height: f64) -> Sensor {
+        Sensor { width, height }
+    }
+    // The vertical field of view in degrees through a lens of this focal length. The image
+    // takes in the full width of the sensor and its height follows from the image shape.
+    pub fn vfov(&self, focal_length: f64, aspect_ratio: f64) -> f64 {
+        let half_height = self.width / aspect_ratio / 2.0;
+        2.0 * (half_height / focal_length).atan().to_degrees()
+    }
+    // the focal length that gives a vertical field of view of vfov degrees
+    pub fn focal_length(&self, vfov: f64, aspect_ratio: f64) -> f64 {
+        let half_height = self.width / aspect_ratio / 2.0;
+        half_height / degrees_to_radians(vfov / 2.0).tan()
+    }
+}
+
+impl FromStr for Sensor {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "full-frame" => Ok(Sensor::new(36.0, 24.0)),
+            "aps-c" => Ok(Sensor::new(23.6, 15.6)),
+            "micro-four-thirds" => Ok(Sensor::new(17.3, 13.0)),
+            _ => {
+                let size = s.split_once('x').and_then(|(width, height)| {
+                    Some((width.parse::<f64>().ok()?, height.parse::<f64>().ok()?))
+                });
+                match size {
+                    Some((width, height)) if width > 0.0 && height > 0.0 => {
+                        Ok(Sensor::new(width, height))
+                    }
+                    _ => Err(format!("unknown sensor {s}")),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Sensor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+// The diameter, in scene units, of the opening of a lens focal_length millimeters long
+// stopped down to f_number. Scene units are taken to be meters.
+pub fn aperture_diameter(focal_length: f64, f_number: f64) -> f64 {
+    focal_length / f_number / 1000.0
+}
+
+// How many stops brighter than the plain render a photograph taken with these settings
+// comes out. It's calibrated on the sunny 16 rule, so f/16 with the shutter open for one over
+// the ISO seconds gives the brightness the renderer has always had.
+pub fn exposure_stops(f_number: f64, shutter: f64, iso: f64) -> f64 {
+    (shutter * iso * 256.0 / (f_number * f_number)).log2()
+}
+
+// Where the camera is and what it looks at, which an animation can change from frame to
+// frame. The field of view and focus distance replace the ones from the options when set.
+pub struct CameraPose {
+    pub lookfrom: Point,
+    pub lookat: Point,
+    pub fov: Option<f64>,
+    pub focus_distance: Option<f64>,
+}
+
+#[allow(dead_code)]
+impl CameraPose {
+    pub fn new(lookfrom: Point, lookat: Point) -> CameraPose {
+        CameraPose {
+            lookfrom,
+            lookat,
+            fov: None,
+            focus_distance: None,
+        }
+    }
+}
+
+// Where a camera sits and which way it faces: w points backward from the view direction, u
+// to the right and v up, like the axes of the image.
+pub struct CameraBasis {
+    pub origin: Point,
+    pub u: Vector3,
+    pub v: Vector3,
+    pub w: Vector3,
+}
+
+impl CameraBasis {
+    pub fn new(lookfrom: Point, lookat: Point, vup: Vector3) -> CameraBasis {
+        let w = Vector3::unit_vector(&(lookfrom.clone() - lookat));
+        let u = Vector3::unit_vector(&Vector3::cross(&vup, &w));
+        let v = Vector3::cross(&w, &u);
+        CameraBasis {
+            origin: lookfrom,
+            u,
+            v,
+            w,
+        }
+    }
+    pub fn clone(&self) -> Self {
+        CameraBasis {
+            origin: self.origin.clone(),
+            u: self.u.clone(),
+            v: self.v.clone(),
+            w: self.w.clone(),
+        }
+    }
+    // the same view from a point moved sideways, to the right for positive distances
+    pub fn offset(&self, distance: f64) -> CameraBasis {
+        let mut basis = self.clone();
+        basis.origin = basis.origin + self.u.clone() * distance;
+        basis
+    }
+    // The same view with the camera held level: v straight up and w horizontal, so only the
+    // way the view faces around the vertical axis is kept.
+    pub fn level(&self, up: &Vector3) -> CameraBasis {
+        let v = Vector3::unit_vector(up);
+        let w = Vector3::unit_vector(&(self.w.clone() - v.clone() * Vector3::dot(&self.w, &v)));
+        let u = Vector3::cross(&v, &w);
+        CameraBasis {
+            origin: self.origin.clone(),
+            u,
+            v,
+            w,
+        }
+    }
+}
+
+// A pinhole camera with a thin lens for depth of field.
+pub struct PerspectiveCamera {
     origin: Point,
     lower_left_corner: Point,
     horizontal: Vector3,
@@ -13,36 +211,44 @@ pub struct Camera {
     u: Vector3,
     v: Vector3,
     lens_radius: f64,
+    aperture: Box<dyn Aperture>,
+    aspect_ratio: f64,
+    vignetting: f64,
+    focus_center: Point,
+    // the direction the plane of focus faces once it's tilted
+    focus_normal: Option<Vector3>,
 }
 
 #[allow(dead_code)]
-impl Camera {
+impl PerspectiveCamera {
     pub fn new(
-        lookfrom: Point,
-        lookat: Point,
-        vup: Vector3,
+        basis: CameraBasis,
         vfov: f64, // vertical fov in degrees
         aspect_ratio: f64,
         aperture: f64,
         focus_dist: f64,
-    ) -> Camera {
+    ) -> PerspectiveCamera {
         let theta = degrees_to_radians(vfov);
         let h = (theta / 2.0).tan();
         let viewport_height = 2.0 * h;
         let viewport_width = aspect_ratio * viewport_height;
 
-        let w = Vector3::unit_vector(&(lookfrom.clone() - lookat.clone()));
-        let u = Vector3::unit_vector(&Vector3::cross(&vup, &w));
-        let v = Vector3::cross(&w, &u);
+        let CameraBasis {
+            origin: lookfrom,
+            u,
+            v,
+            w,
+        } = basis;
 
         let horizontal = u.clone() * viewport_width * focus_dist;
         let vertical = v.clone() * viewport_height * focus_dist;
+        let focus_center = lookfrom.clone() - w.clone() * focus_dist;
         let lower_left_corner =
             lookfrom.clone() - horizontal.clone() / 2.0 - vertical.clone() / 2.0 - (w * focus_dist);
 
         let lens_radius = aperture / 2.0;
 
-        Camera {
+        PerspectiveCamera {
             origin: lookfrom,
             lower_left_corner,
             horizontal,
@@ -50,19 +256,101 @@ impl Camera {
             u,
             v,
             lens_radius,
+            aperture: Box::new(CircularAperture),
+            aspect_ratio,
+            vignetting: 0.0,
+            focus_center,
+            focus_normal: None,
+        }
+    }
+    // Slides the image sideways by shift world units at the focus distance without turning
+    // the camera, like the shift of an off-axis stereo frustum.
+    pub fn with_shift(mut self, shift: f64) -> Self {
+        self.lower_left_corner = self.lower_left_corner + self.u.clone() * shift;
+        self
+    }
+    // Slides the image across the sensor by fractions of its width and height, like the shift
+    // of a tilt-shift lens. Pointing the camera level and shifting up takes in a tall building
+    // with its walls still standing straight.
+    pub fn with_lens_shift(mut self, x: f64, y: f64) -> Self {
+        self.lower_left_corner =
+            self.lower_left_corner + self.horizontal.clone() * x + self.vertical.clone() * y;
+        self
+    }
+    // Turns the plane of focus about the point straight ahead at the focus distance, as
+    // tilting the lens against the sensor does by the Scheimpflug principle. A positive tilt
+    // leans the top of the plane away from the camera, so it can lie along the ground, and a
+    // positive swing turns its right side away. Both are in degrees.
+    pub fn with_tilt(mut self, tilt: f64, swing: f64) -> Self {
+        if tilt == 0.0 && swing == 0.0 {
+            self.focus_normal = None;
+            return self;
         }
+        let w = Vector3::cross(&self.u, &self.v);
+        let normal = w
+            + self.v.clone() * degrees_to_radians(tilt).tan()
+            + self.u.clone() * degrees_to_radians(swing).tan();
+        self.focus_normal = Some(Vector3::unit_vector(&normal));
+        self
+    }
+    // the shape of the opening, round by default
+    pub fn with_aperture(mut self, aperture: Box<dyn Aperture>) -> Self {
+        self.aperture = aperture;
+        self
+    }
+    // Optical vignetting, where the lens barrel hides part of the aperture from points away
+    // from the center of the image. Out of focus highlights there get cut into cat's-eye
+    // shapes and the corners darken. At 1 the barrel reaches the middle of the aperture in
+    // the corners of the image.
+    pub fn with_vignetting(mut self, vignetting: f64) -> Self {
+        self.vignetting = vignetting;
+        self
     }
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd = Vector3::random_in_unit_disk() * self.lens_radius;
-        let offset = self.u.clone() * rd.x() + self.v.clone() * rd.y();
+}
 
-        Ray::new(
+impl Camera for PerspectiveCamera {
+    fn get_ray(&self, s: f64, t: f64, sampler: &mut dyn Sampler) -> Option<Ray> {
+        let (x, y) = self.aperture.sample(sampler.get_2d());
+        if self.vignetting > 0.0 {
+            // the barrel's opening is as wide as the aperture and slides across it toward the
+            // edge of the image, blocking whatever falls outside
+            let scale = self.vignetting / (self.aspect_ratio * self.aspect_ratio + 1.0).sqrt();
+            let bx = x - (2.0 * s - 1.0) * self.aspect_ratio * scale;
+            let by = y - (2.0 * t - 1.0) * scale;
+            if bx * bx + by * by > 1.0 {
+                return None;
+            }
+        }
+        let offset =
+            self.u.clone() * (x * self.lens_radius) + self.v.clone() * (y * self.lens_radius);
+
+        if let Some(normal) = &self.focus_normal {
+            // follow the ray through the middle of the lens out to the tilted plane of focus,
+            // where the rays through the rest of the lens meet it
+            let direction = self.lower_left_corner.clone()
+                + self.horizontal.clone() * s
+                + self.vertical.clone() * t
+                - self.origin.clone();
+            let along = Vector3::dot(&direction, normal);
+            let distance =
+                Vector3::dot(&(self.focus_center.clone() - self.origin.clone()), normal) / along;
+            if !distance.is_finite() || distance <= 0.0 {
+                // the plane is behind the camera along this ray, so it's in focus far away
+                return Some(Ray::new(self.origin.clone() + offset, direction));
+            }
+            let focus = self.origin.clone() + direction * distance;
+            return Some(Ray::new(
+                self.origin.clone() + offset.clone(),
+                focus - self.origin.clone() - offset,
+            ));
+        }
+        Some(Ray::new(
             self.origin.clone() + offset.clone(),
             self.lower_left_corner.clone()
                 + self.horizontal.clone() * s
                 + self.vertical.clone() * t
                 - self.origin.clone()
                 - offset,
-        )
+        ))
     }
 }
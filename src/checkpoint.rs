@@ -0,0 +1,232 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Result, Write};
+
+use crate::{
+    color::Color,
+    film::{Film, Pixel},
+    image::{invalid_data, next_line, next_token},
+    options::Options,
+};
+
+const MAGIC: &str = "ray-tracer-checkpoint";
+const VERSION: u32 = 2;
+
+// Saves a render in progress: the settings that decide what the image looks like, followed
+// by everything accumulated for each pixel. Every sample is fully determined by the seed,
+// its pixel and its index, so picking up from here gives the image an uninterrupted render
+// would have. Filters wider than a pixel add up their neighbors' samples in a different
+// order, so the result is identical up to rounding.
+//
+// The file starts with text lines, "arg" lines holding the settings as command line
+// arguments, then a "data" line followed by raw little endian pixel values.
+pub fn save(file_name: &str, options: &Options, film: &Film) -> Result<()> {
+    // write to a temporary file first so a crash mid-save can't ruin the last checkpoint
+    let temporary = format!("{file_name}.tmp");
+    {
+        let mut f = BufWriter::new(File::create(&temporary)?);
+        writeln!(f, "{MAGIC} {VERSION}")?;
+        writeln!(f, "size {} {}", film.width(), film.height())?;
+        for arg in options.render_settings() {
+            writeln!(f, "arg {arg}")?;
+        }
+        writeln!(f, "data")?;
+        for row in 0..film.height() {
+            for x in 0..film.width() {
+                let (sum, weight, samples, mean, m2) = film.pixel(x, row).parts();
+                for value in [sum.red(), sum.green(), sum.blue(), weight, mean, m2] {
+                    f.write_all(&value.to_bits().to_le_bytes())?;
+                }
+                f.write_all(&(samples as u64).to_le_bytes())?;
+            }
+        }
+        f.flush()?;
+    }
+    fs::rename(&temporary, file_name)
+}
+
+// Loads a checkpoint, copying its settings over options so the render continues the same
+// way. Settings about how long to keep going, like --samples, are left as they were.
+pub fn load(file_name: &str, options: &mut Options) -> Result<Film> {
+    let bytes = fs::read(file_name)?;
+    let mut pos = 0;
+    if next_token(&bytes, &mut pos)? != MAGIC {
+        return Err(invalid_data("not a ray tracer checkpoint"));
+    }
+    if next_token(&bytes, &mut pos)? != VERSION.to_string() {
+        return Err(invalid_data("unsupported checkpoint version"));
+    }
+
+    let mut size = None;
+    let mut args = Vec::new();
+    loop {
+        let line = next_line(&bytes, &mut pos)?;
+        if line == "data" {
+            break;
+        }
+        if let Some(arg) = line.strip_prefix("arg ") {
+            args.push(arg.to_string());
+        } else if let Some(dimensions) = line.strip_prefix("size ") {
+            let parts: Vec<i32> = dimensions
+                .split_whitespace()
+                .filter_map(|n| n.parse().ok())
+                .collect();
+            if parts.len() != 2 {
+                return Err(invalid_data("bad checkpoint size"));
+            }
+            size = Some((parts[0], parts[1]));
+        } else if !line.is_empty() {
+            return Err(invalid_data("unknown line in checkpoint"));
+        }
+    }
+    let (width, height) = size.ok_or_else(|| invalid_data("checkpoint has no size"))?;
+    let saved = Options::parse(args.into_iter()).map_err(|message| invalid_data(&message))?;
+    options.apply_render_settings(&saved);
+
+    let mut film = Film::new(width, height);
+    let mut data = bytes[pos..].chunks_exact(8);
+    let mut next = || -> Result<u64> {
+        let chunk = data
+            .next()
+            .ok_or_else(|| invalid_data("checkpoint ended early"))?;
+        Ok(u64::from_le_bytes(chunk.try_into().unwrap()))
+    };
+    for row in 0..height {
+        for x in 0..width {
+            let mut values = [0.0; 6];
+            for value in values.iter_mut() {
+                *value = f64::from_bits(next()?);
+            }
+            let [r, g, b, weight, mean, m2] = values;
+            let samples = next()? as u32;
+            let sum = Color::new(r, g, b);
+            *film.pixel_mut(x, row) = Pixel::from_parts(sum, weight, samples, mean, m2);
+        }
+    }
+    Ok(film)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::{
+        aov::AovKind, background::SkyGradient, camera::CameraBasis, filter::create_filter,
+        hittable_vec::HittableVec, lambertian::Lambertian, orthographic::OrthographicCamera,
+        point::Point, render::Renderer, sampler::create_sampler, sphere::Sphere, vector3::Vector3,
+    };
+
+    fn options(samples: u32, filter: &str) -> Options {
+        let samples = samples.to_string();
+        let args = [
+            "--samples",
+            &samples,
+            "--sampler",
+            "sobol",
+            "--filter",
+            filter,
+        ];
+        Options::parse(args.iter().map(|arg| arg.to_string())).unwrap()
+    }
+
+    // a small sphere on the ground, seen from the front
+    fn render(film: &mut Film, options: &Options) {
+        let gray = Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let mut world = HittableVec::new();
+        world.push(Box::new(Sphere::new(
+            Point::new(0.0, -100.5, 0.0),
+            100.0,
+            gray.clone(),
+        )));
+        world.push(Box::new(Sphere::new(Point::origin(), 0.5, gray)));
+        let basis = CameraBasis::new(
+            Point::new(0.0, 0.0, 3.0),
+            Point::origin(),
+            Vector3::new(0.0, 1.0, 0.0),
+        );
+        let camera = OrthographicCamera::new(basis, 3.0, 2.0);
+        let background = SkyGradient::new();
+        let filter = create_filter(options.filter, options.filter_radius());
+        let mut sampler = create_sampler(options.sampler, options.strata(), options.seed);
+        let renderer = Renderer::new(&world, &background, &camera, filter.as_ref(), options);
+        assert!(renderer.render(film, sampler.as_mut()));
+    }
+
+    // renders the scene straight through, and again stopping part way to save and resume
+    fn straight_and_resumed(filter: &str) -> (Film, Film) {
+        let mut straight = Film::new(12, 8);
+        straight.record_aovs();
+        render(&mut straight, &options(8, filter));
+
+        let mut first = Film::new(12, 8);
+        render(&mut first, &options(3, filter));
+        let path =
+            std::env::temp_dir().join(format!("resume-test-{filter}-{}.ckpt", std::process::id()));
+        let file_name = path.to_str().unwrap();
+        save(file_name, &options(3, filter), &first).unwrap();
+        let mut resumed_options = options(8, filter);
+        let mut resumed = load(file_name, &mut resumed_options).unwrap();
+        fs::remove_file(file_name).unwrap();
+        resumed.record_aovs();
+        render(&mut resumed, &resumed_options);
+        (straight, resumed)
+    }
+
+    #[test]
+    fn resumed_render_matches_an_uninterrupted_one() {
+        let (straight, resumed) = straight_and_resumed("box");
+        for row in 0..8 {
+            for x in 0..12 {
+                let (sum, weight, samples, mean, m2) = straight.pixel(x, row).parts();
+                let (resumed_sum, resumed_weight, resumed_samples, resumed_mean, resumed_m2) =
+                    resumed.pixel(x, row).parts();
+                assert_eq!(samples, resumed_samples);
+                assert_eq!(mean.to_bits(), resumed_mean.to_bits());
+                assert_eq!(m2.to_bits(), resumed_m2.to_bits());
+                assert_eq!(weight.to_bits(), resumed_weight.to_bits());
+                assert_eq!(sum.red().to_bits(), resumed_sum.red().to_bits());
+                assert_eq!(sum.green().to_bits(), resumed_sum.green().to_bits());
+                assert_eq!(sum.blue().to_bits(), resumed_sum.blue().to_bits());
+            }
+        }
+    }
+
+    #[test]
+    fn resuming_traces_the_aovs_of_earlier_samples_again() {
+        let (straight, resumed) = straight_and_resumed("box");
+        for kind in [AovKind::Albedo, AovKind::Normal, AovKind::Depth] {
+            let image = straight.aovs().unwrap().image(kind);
+            let resumed_image = resumed.aovs().unwrap().image(kind);
+            for y in 0..8 {
+                for x in 0..12 {
+                    let (color, resumed_color) = (image.pixel(x, y), resumed_image.pixel(x, y));
+                    assert_eq!(color.red().to_bits(), resumed_color.red().to_bits());
+                    assert_eq!(color.green().to_bits(), resumed_color.green().to_bits());
+                    assert_eq!(color.blue().to_bits(), resumed_color.blue().to_bits());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wide_filters_only_change_the_rounding() {
+        let (straight, resumed) = straight_and_resumed("gaussian");
+        for row in 0..8 {
+            for x in 0..12 {
+                let (sum, weight, samples, mean, m2) = straight.pixel(x, row).parts();
+                let (resumed_sum, resumed_weight, resumed_samples, resumed_mean, resumed_m2) =
+                    resumed.pixel(x, row).parts();
+                // a pixel's own samples arrive in the same order either way
+                assert_eq!(samples, resumed_samples);
+                assert_eq!(mean.to_bits(), resumed_mean.to_bits());
+                assert_eq!(m2.to_bits(), resumed_m2.to_bits());
+                // but the filter splats its neighbors' samples in a different order
+                let close = |a: f64, b: f64| (a - b).abs() <= 1e-12 * a.abs().max(1.0);
+                assert!(close(weight, resumed_weight));
+                assert!(close(sum.red(), resumed_sum.red()));
+                assert!(close(sum.green(), resumed_sum.green()));
+                assert!(close(sum.blue(), resumed_sum.blue()));
+            }
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{
+    color::Color,
+    dielectric::Dielectric,
+    hittable::HitRecord,
+    material::{Material, ScatterResult},
+    ray::Ray,
+    sampler::Sampler,
+    vector3::Vector3,
+};
+
+// A clear dielectric layer over any other material, like the clearcoat on car paint or the
+// varnish on wood. Light is either reflected off the coat by Fresnel or passes through it to
+// the base material and picks up the coat's absorption on the way in and back out.
+pub struct Coated<M: Material> {
+    base: M,
+    ir: f64,        // index of refraction of the coat
+    roughness: f64, // 0 gives a mirror smooth coat, like Metal's fuzz
+    absorption: Color,
+    thickness: f64,
+}
+
+#[allow(dead_code)]
+impl<M: Material> Coated<M> {
+    pub fn new(base: M, ir: f64, roughness: f64) -> Coated<M> {
+        Coated {
+            base,
+            ir,
+            roughness: roughness.min(1.0),
+            absorption: Color::new(0.0, 0.0, 0.0),
+            thickness: 0.0,
+        }
+    }
+    pub fn with_absorption(mut self, absorption: Color, thickness: f64) -> Coated<M> {
+        // absorption is the per unit length absorption coefficient of each color channel
+        self.absorption = absorption;
+        self.thickness = thickness;
+        self
+    }
+
+    // cosine of the angle a ray makes with the normal once it has refracted into the coat
+    fn refracted_cosine(&self, cosine: f64) -> f64 {
+        let sin_t = (1.0 - cosine * cosine).max(0.0).sqrt() / self.ir;
+        (1.0 - sin_t * sin_t).max(0.0).sqrt()
+    }
+
+    fn transmittance(&self, cos_in: f64, cos_out: f64) -> Color {
+        let distance = self.thickness
+            * (1.0 / self.refracted_cosine(cos_in).max(1e-4)
+                + 1.0 / self.refracted_cosine(cos_out).max(1e-4));
+        Color::new(
+            (-self.absorption.red() * distance).exp(),
+            (-self.absorption.green() * distance).exp(),
+            (-self.absorption.blue() * distance).exp(),
+        )
+    }
+}
+
+impl<M: Material> Material for Coated<M> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> ScatterResult {
+        let unit_direction = Vector3::unit_vector(r_in.direction());
+        let cos_in = Vector3::dot(&(-unit_direction.clone()), &rec.normal).min(1.0);
+
+        if Dielectric::reflectance(cos_in, 1.0 / self.ir) > sampler.get_1d() {
+            // bounced straight off the coat, which doesn't tint the light
+            let reflected = Vector3::reflect(&unit_direction, &rec.normal);
+            let scattered = Ray::new(
+                rec.point.clone(),
+                reflected
+                    + Vector3::sample_in_unit_sphere(sampler.get_2d(), sampler.get_1d())
+                        * self.roughness,
+            );
+            let success = Vector3::dot(scattered.direction(), &rec.normal) > 0.0
+                && !rec.below_surface(scattered.direction());
+            return ScatterResult {
+                success,
+                attenuation: Color::new(1.0, 1.0, 1.0),
+                scattered,
+            };
+        }
+
+        // made it through the coat, so let the base decide where the light goes next
+        let base_result = self.base.scatter(r_in, rec, sampler);
+        if !base_result.success {
+            return base_result;
+        }
+        let cos_out = Vector3::dot(
+            &Vector3::unit_vector(base_result.scattered.direction()),
+            &rec.normal,
+        );
+        if cos_out <= 0.0 || rec.below_surface(base_result.scattered.direction()) {
+            return ScatterResult::new(false, base_result.attenuation, base_result.scattered);
+        }
+        // whatever reflects back down at the top of the coat on the way out is treated as lost
+        let exit = 1.0 - Dielectric::reflectance(cos_out.min(1.0), 1.0 / self.ir);
+        let attenuation = base_result.attenuation * self.transmittance(cos_in, cos_out) * exit;
+        ScatterResult::new(true, attenuation, base_result.scattered)
+    }
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.base.albedo(rec)
+    }
+}
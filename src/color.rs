@@ -1,8 +1,5 @@
-use std::fs::File;
-use std::io::{BufWriter, Write};
 use std::ops::{Add, AddAssign, Mul};
 
-use crate::utility;
 use crate::vector3::Vector3;
 
 pub struct Color {
@@ -34,21 +31,6 @@ impl Color {
             blue: self.blue,
         }
     }
-    pub fn write(&self, f: &mut BufWriter<File>, samples_per_pixel: f64) {
-        let max = 256.0;
-        let scale = 1.0 / samples_per_pixel;
-        let r = utility::clamp((self.red() * scale).sqrt(), 0.0, 0.999);
-        let g = utility::clamp((self.green() * scale).sqrt(), 0.0, 0.999);
-        let b = utility::clamp((self.blue() * scale).sqrt(), 0.0, 0.999);
-        writeln!(
-            f,
-            "{} {} {}",
-            (max * r) as i32,
-            (max * g) as i32,
-            (max * b) as i32
-        )
-        .expect("unable to write");
-    }
     pub fn red(&self) -> f64 {
         self.red
     }
@@ -58,6 +40,9 @@ impl Color {
     pub fn blue(&self) -> f64 {
         self.blue
     }
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.red + 0.7152 * self.green + 0.0722 * self.blue
+    }
 }
 
 impl Add for Color {
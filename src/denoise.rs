@@ -0,0 +1,177 @@
+use crate::{
+    aov::{AovFilm, AovKind},
+    color::Color,
+    image::Image,
+};
+
+// how far away, in pixels, similar pixels are looked for
+const SEARCH_RADIUS: i32 = 7;
+// half the width of the patches compared to decide whether two pixels are alike
+const PATCH_RADIUS: i32 = 1;
+// how far apart the features of two pixels can be before they stop being averaged together
+const NORMAL_SIGMA: f64 = 0.3;
+const ALBEDO_SIGMA: f64 = 0.1;
+// relative to the distance
+const DEPTH_SIGMA: f64 = 0.1;
+// albedo below this isn't divided out, it would only blow up the noise
+const MIN_ALBEDO: f64 = 0.01;
+
+// A non-local means filter guided by the aov features, in the style of Rousselle et al.'s
+// "Adaptive Rendering with Non-Local Means Filtering". Each pixel becomes a weighted average
+// of the pixels around it. A neighbor counts for more the more its patch of colors looks like
+// the pixel's own, after allowing for how noisy both are, and only as much as its normal,
+// albedo and depth match. The albedo is divided out first and put back afterwards, so
+// textures stay sharp while the lighting on them is smoothed.
+pub struct Denoiser {
+    strength: f64,
+}
+
+impl Denoiser {
+    // strength scales how different two patches can look and still be averaged, larger is
+    // smoother
+    pub fn new(strength: f64) -> Denoiser {
+        Denoiser { strength }
+    }
+
+    // color is the linear image, variance the variance of each pixel's brightness.
+    pub fn denoise(&self, color: &Image, variance: &[f64], aovs: &AovFilm) -> Image {
+        let width = color.width() as i32;
+        let height = color.height() as i32;
+        let albedo = aovs.image(AovKind::Albedo);
+        let normal = aovs.image(AovKind::Normal);
+        let depth = aovs.image(AovKind::Depth);
+
+        let mut divisor = Vec::with_capacity(color.pixels().len());
+        let mut signal = Vec::with_capacity(color.pixels().len());
+        let mut noise = Vec::with_capacity(color.pixels().len());
+        for (i, c) in color.pixels().iter().enumerate() {
+            let a = &albedo.pixels()[i];
+            let d = [a.red(), a.green(), a.blue()].map(|a| if a < MIN_ALBEDO { 1.0 } else { a });
+            let luminance = Color::new(d[0], d[1], d[2]).luminance();
+            signal.push([c.red() / d[0], c.green() / d[1], c.blue() / d[2]]);
+            noise.push(variance[i] / (luminance * luminance));
+            divisor.push(d);
+        }
+        // the variance from a handful of samples is itself noisy, smooth it a little
+        let noise: Vec<f64> = (0..noise.len())
+            .map(|i| {
+                let (x, y) = ((i % color.width()) as i32, (i / color.width()) as i32);
+                let mut total = 0.0;
+                let mut count = 0.0;
+                for ny in (y - 1).max(0)..=(y + 1).min(color.height() as i32 - 1) {
+                    for nx in (x - 1).max(0)..=(x + 1).min(color.width() as i32 - 1) {
+                        total += noise[ny as usize * color.width() + nx as usize];
+                        count += 1.0;
+                    }
+                }
+                total / count
+            })
+            .collect();
+        let features: Vec<Features> = (0..signal.len())
+            .map(|i| Features {
+                normal: channels(&normal.pixels()[i]),
+                albedo: channels(&albedo.pixels()[i]),
+                depth: depth.pixels()[i].red(),
+            })
+            .collect();
+
+        let index = |x: i32, y: i32| (y * width + x) as usize;
+        let k2 = self.strength * self.strength;
+        let mut sum = vec![[0.0; 3]; signal.len()];
+        let mut weight_sum = vec![0.0; signal.len()];
+        let mut distance = vec![f64::NAN; signal.len()];
+        for dy in -SEARCH_RADIUS..=SEARCH_RADIUS {
+            for dx in -SEARCH_RADIUS..=SEARCH_RADIUS {
+                // how different each pixel is from the one at this offset, with the part that
+                // is only down to noise taken away
+                for y in 0..height {
+                    for x in 0..width {
+                        let (qx, qy) = (x + dx, y + dy);
+                        let p = index(x, y);
+                        if qx < 0 || qx >= width || qy < 0 || qy >= height {
+                            distance[p] = f64::NAN;
+                            continue;
+                        }
+                        let q = index(qx, qy);
+                        let (vp, vq) = (noise[p], noise[q]);
+                        let d: f64 = signal[p]
+                            .iter()
+                            .zip(&signal[q])
+                            .map(|(a, b)| {
+                                ((a - b) * (a - b) - (vp + vp.min(vq))) / (1e-10 + k2 * (vp + vq))
+                            })
+                            .sum();
+                        distance[p] = d / 3.0;
+                    }
+                }
+                // averaged over the patch around each pixel
+                for y in 0..height {
+                    for x in 0..width {
+                        let (qx, qy) = (x + dx, y + dy);
+                        if qx < 0 || qx >= width || qy < 0 || qy >= height {
+                            continue;
+                        }
+                        let mut patch = 0.0;
+                        let mut count = 0;
+                        for py in (y - PATCH_RADIUS).max(0)..=(y + PATCH_RADIUS).min(height - 1) {
+                            for px in (x - PATCH_RADIUS).max(0)..=(x + PATCH_RADIUS).min(width - 1)
+                            {
+                                let d = distance[index(px, py)];
+                                if !d.is_nan() {
+                                    patch += d;
+                                    count += 1;
+                                }
+                            }
+                        }
+                        let (p, q) = (index(x, y), index(qx, qy));
+                        let color_weight = (-(patch / count as f64).max(0.0)).exp();
+                        let weight = color_weight.min(features[p].weight(&features[q]));
+                        for c in 0..3 {
+                            sum[p][c] += weight * signal[q][c];
+                        }
+                        weight_sum[p] += weight;
+                    }
+                }
+            }
+        }
+
+        // every pixel has at least its own weight of 1
+        let pixels = (0..signal.len())
+            .map(|i| {
+                let [r, g, b] = sum[i].map(|s| s / weight_sum[i]);
+                let d = divisor[i];
+                Color::new(r * d[0], g * d[1], b * d[2])
+            })
+            .collect();
+        Image::new(color.width(), color.height(), pixels)
+    }
+}
+
+// what the camera sees at a pixel, besides its color
+struct Features {
+    normal: [f64; 3],
+    albedo: [f64; 3],
+    depth: f64,
+}
+
+impl Features {
+    fn weight(&self, other: &Features) -> f64 {
+        let squared = |a: &[f64; 3], b: &[f64; 3]| -> f64 {
+            a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+        };
+        let normal = squared(&self.normal, &other.normal) / (NORMAL_SIGMA * NORMAL_SIGMA);
+        let albedo = squared(&self.albedo, &other.albedo) / (ALBEDO_SIGMA * ALBEDO_SIGMA);
+        let scale = self.depth.max(other.depth) * DEPTH_SIGMA;
+        let depth = if scale > 0.0 {
+            let relative = (self.depth - other.depth) / scale;
+            relative * relative
+        } else {
+            0.0
+        };
+        (-(normal + albedo + depth)).exp()
+    }
+}
+
+fn channels(color: &Color) -> [f64; 3] {
+    [color.red(), color.green(), color.blue()]
+}
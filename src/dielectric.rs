@@ -1,28 +1,70 @@
 use crate::{
     color::Color, hittable::HitRecord, material::Material, material::ScatterResult, ray::Ray,
-    utility, vector3::Vector3,
+    sampler::Sampler, thin_film::ThinFilm, vector3::Vector3,
 };
 
 pub struct Dielectric {
     ir: f64, // Index of Refraction (for air is 1.0, glass somewhere between 1.3-1.7, diamond 2.4, etc)
+    film: Option<ThinFilm>,
 }
 
 #[allow(dead_code)]
 impl Dielectric {
     pub fn new(ir: f64) -> Dielectric {
-        Dielectric { ir }
+        Dielectric { ir, film: None }
+    }
+    pub fn with_thin_film(mut self, film: ThinFilm) -> Dielectric {
+        self.film = Some(film);
+        self
     }
 
-    fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
+    pub fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
         // We use Schlick's approximation for reflectance
         let r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
         let r0 = r0 * r0;
         r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
     }
+
+    fn scatter_through_film(
+        &self,
+        film: &ThinFilm,
+        unit_direction: &Vector3,
+        rec: &HitRecord,
+        cos_theta: f64,
+        sampler: &mut dyn Sampler,
+    ) -> ScatterResult {
+        // the film sits on the outside of the surface, so which side we come from decides
+        // the order of the layers
+        let (outside_ir, inside_ir) = if rec.front_face {
+            (1.0, self.ir)
+        } else {
+            (self.ir, 1.0)
+        };
+        let reflectance = film.reflectance(cos_theta, outside_ir, [inside_ir; 3]);
+        let reflect_probability =
+            (reflectance.red() + reflectance.green() + reflectance.blue()) / 3.0;
+
+        // pick reflection or refraction by the average reflectance and let the attenuation
+        // carry the difference between the color channels
+        if reflect_probability > sampler.get_1d() {
+            let attenuation = reflectance * (1.0 / reflect_probability);
+            let direction = Vector3::reflect(unit_direction, &rec.normal);
+            ScatterResult::new(true, attenuation, Ray::new(rec.point.clone(), direction))
+        } else {
+            let transmittance = Color::new(
+                1.0 - reflectance.red(),
+                1.0 - reflectance.green(),
+                1.0 - reflectance.blue(),
+            );
+            let attenuation = transmittance * (1.0 / (1.0 - reflect_probability));
+            let direction = Vector3::refract(unit_direction, &rec.normal, outside_ir / inside_ir);
+            ScatterResult::new(true, attenuation, Ray::new(rec.point.clone(), direction))
+        }
+    }
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> ScatterResult {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> ScatterResult {
         let attenuation = Color::new(1.0, 1.0, 1.0);
         let refraction_ratio = if rec.front_face {
             1.0 / self.ir
@@ -36,8 +78,14 @@ impl Material for Dielectric {
 
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
 
+        if let Some(film) = &self.film {
+            if !cannot_refract {
+                return self.scatter_through_film(film, &unit_direction, rec, cos_theta, sampler);
+            }
+        }
+
         let direction = if cannot_refract
-            || Dielectric::reflectance(cos_theta, refraction_ratio) > utility::rand()
+            || Dielectric::reflectance(cos_theta, refraction_ratio) > sampler.get_1d()
         {
             Vector3::reflect(&unit_direction, &rec.normal)
         } else {
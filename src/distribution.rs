@@ -0,0 +1,145 @@
+// Piecewise constant probability distributions, used to pick samples in proportion to some
+// tabulated function such as the brightness of an environment map.
+
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    func_int: f64,
+}
+
+#[allow(dead_code)]
+impl Distribution1D {
+    pub fn new(func: Vec<f64>) -> Distribution1D {
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 1..=n {
+            cdf[i] = cdf[i - 1] + func[i - 1].abs() / n as f64;
+        }
+        let func_int = cdf[n];
+        for (i, value) in cdf.iter_mut().enumerate().skip(1) {
+            // an all zero function falls back to picking uniformly
+            *value = if func_int == 0.0 {
+                i as f64 / n as f64
+            } else {
+                *value / func_int
+            };
+        }
+        Distribution1D {
+            func,
+            cdf,
+            func_int,
+        }
+    }
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+    pub fn integral(&self) -> f64 {
+        self.func_int
+    }
+
+    // Turns a uniform u in [0, 1) into a sample x in [0, 1), returning x, its pdf and the
+    // index of the piece it landed in.
+    pub fn sample_continuous(&self, u: f64) -> (f64, f64, usize) {
+        // last cdf entry that is still <= u
+        let offset = self.cdf.partition_point(|&c| c <= u).clamp(1, self.count()) - 1;
+        let mut du = u - self.cdf[offset];
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        if width > 0.0 {
+            du /= width;
+        }
+        let pdf = self.pdf_of(offset);
+        ((offset as f64 + du) / self.count() as f64, pdf, offset)
+    }
+
+    fn pdf_of(&self, offset: usize) -> f64 {
+        if self.func_int > 0.0 {
+            self.func[offset].abs() / self.func_int
+        } else {
+            1.0
+        }
+    }
+}
+
+// A 2D distribution over [0, 1)^2 built from a grid of values stored row by row. A row is
+// chosen from the marginal distribution first, then a column within it.
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+#[allow(dead_code)]
+impl Distribution2D {
+    pub fn new(func: &[f64], width: usize, height: usize) -> Distribution2D {
+        let conditional: Vec<Distribution1D> = func
+            .chunks_exact(width)
+            .take(height)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|c| c.integral()).collect());
+        Distribution2D {
+            conditional,
+            marginal,
+        }
+    }
+
+    // Returns (u, v) where u runs along a row and v down the rows, and the pdf of the sample.
+    pub fn sample_continuous(&self, u1: f64, u2: f64) -> ((f64, f64), f64) {
+        let (v, pdf_v, row) = self.marginal.sample_continuous(u2);
+        let (u, pdf_u, _) = self.conditional[row].sample_continuous(u1);
+        ((u, v), pdf_u * pdf_v)
+    }
+
+    pub fn pdf(&self, u: f64, v: f64) -> f64 {
+        let width = self.conditional[0].count();
+        let height = self.marginal.count();
+        let x = ((u * width as f64) as usize).min(width - 1);
+        let y = ((v * height as f64) as usize).min(height - 1);
+        if self.marginal.integral() == 0.0 {
+            return 1.0;
+        }
+        self.conditional[y].func[x].abs() / self.marginal.integral()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_pdf_integrates_to_one() {
+        let func = [0.0, 1.0, 4.0, 2.0, 0.5, 3.0];
+        let distribution = Distribution1D::new(func.to_vec());
+        let n = distribution.count();
+        let total: f64 = (0..n).map(|i| distribution.pdf_of(i) / n as f64).sum();
+        assert!((total - 1.0).abs() < 1e-12);
+
+        // func as 3 rows of 2, each cell 1/6 of the unit square
+        let distribution = Distribution2D::new(&func, 2, 3);
+        let mut total = 0.0;
+        for y in 0..3 {
+            for x in 0..2 {
+                let (u, v) = ((x as f64 + 0.5) / 2.0, (y as f64 + 0.5) / 3.0);
+                total += distribution.pdf(u, v) / 6.0;
+            }
+        }
+        assert!((total - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn sampling_inverts_the_cdf() {
+        let distribution = Distribution1D::new(vec![1.0, 0.0, 3.0, 4.0]);
+        // the cdf climbs linearly across each piece
+        let cdf = |x: f64| {
+            let piece = ((x * 4.0) as usize).min(3);
+            let within = x * 4.0 - piece as f64;
+            distribution.cdf[piece]
+                + within * (distribution.cdf[piece + 1] - distribution.cdf[piece])
+        };
+        for x in [0.05, 0.2, 0.55, 0.6, 0.75, 0.99] {
+            let (sampled, pdf, piece) = distribution.sample_continuous(cdf(x));
+            assert!((sampled - x).abs() < 1e-12, "{x} came back as {sampled}");
+            assert_eq!(piece, (x * 4.0) as usize);
+            assert!((pdf - distribution.pdf_of(piece)).abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use crate::{
+    background::Background,
+    color::Color,
+    distribution::Distribution2D,
+    image::Image,
+    sampler::Sampler,
+    utility::{self, PI},
+    vector3::Vector3,
+};
+
+// Lights the scene with an equirectangular (latitude/longitude) image wrapped around it,
+// like a .hdr panorama of the sky. Bright texels are sampled more often so small light
+// sources such as the sun are found quickly.
+pub struct EnvironmentMap {
+    image: Image,
+    rotation: f64, // about the y axis in radians
+    intensity: f64,
+    distribution: Distribution2D,
+}
+
+#[allow(dead_code)]
+impl EnvironmentMap {
+    pub fn new(image: Image, rotation: f64, intensity: f64) -> EnvironmentMap {
+        // weight each texel by how much solid angle it covers, which shrinks near the poles
+        let width = image.width();
+        let height = image.height();
+        let mut func = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let sin_theta = (PI * (y as f64 + 0.5) / height as f64).sin();
+            for x in 0..width {
+                func.push(image.pixel(x, y).luminance().max(0.0) * sin_theta);
+            }
+        }
+        let distribution = Distribution2D::new(&func, width, height);
+        EnvironmentMap {
+            image,
+            rotation: utility::degrees_to_radians(rotation),
+            intensity,
+            distribution,
+        }
+    }
+    pub fn load(file_name: &str, rotation: f64, intensity: f64) -> std::io::Result<EnvironmentMap> {
+        Ok(EnvironmentMap::new(
+            Image::load(file_name)?,
+            rotation,
+            intensity,
+        ))
+    }
+
+    // Image coordinates of a direction, u across and v down from the top row. This matches
+    // the texture coordinates of a sphere, with v flipped to run top to bottom.
+    fn direction_to_uv(&self, direction: &Vector3) -> (f64, f64) {
+        let d = rotate_y(&Vector3::unit_vector(direction), -self.rotation);
+        let theta = (-d.y()).clamp(-1.0, 1.0).acos();
+        let phi = (-d.z()).atan2(d.x()) + PI;
+        (phi / (2.0 * PI), 1.0 - theta / PI)
+    }
+    fn uv_to_direction(&self, u: f64, v: f64) -> Vector3 {
+        let theta = PI * (1.0 - v);
+        let phi = 2.0 * PI * u;
+        let d = Vector3::new(
+            -phi.cos() * theta.sin(),
+            -theta.cos(),
+            phi.sin() * theta.sin(),
+        );
+        rotate_y(&d, self.rotation)
+    }
+    fn lookup(&self, u: f64, v: f64) -> Color {
+        let width = self.image.width();
+        let height = self.image.height();
+        let x = ((u * width as f64) as usize).min(width - 1);
+        let y = ((v * height as f64) as usize).min(height - 1);
+        self.image.pixel(x, y).clone() * self.intensity
+    }
+}
+
+impl Background for EnvironmentMap {
+    fn value(&self, direction: &Vector3) -> Color {
+        let (u, v) = self.direction_to_uv(direction);
+        self.lookup(u, v)
+    }
+
+    fn sample(&self, sampler: &mut dyn Sampler) -> Option<(Vector3, Color, f64)> {
+        let (u1, u2) = sampler.get_2d();
+        let ((u, v), map_pdf) = self.distribution.sample_continuous(u1, u2);
+        if map_pdf == 0.0 {
+            return None;
+        }
+        // convert the density over the image into one over directions
+        let sin_theta = (PI * v).sin();
+        if sin_theta == 0.0 {
+            return None;
+        }
+        let pdf = map_pdf / (2.0 * PI * PI * sin_theta);
+        Some((self.uv_to_direction(u, v), self.lookup(u, v), pdf))
+    }
+
+    fn pdf(&self, direction: &Vector3) -> f64 {
+        let (u, v) = self.direction_to_uv(direction);
+        let sin_theta = (PI * v).sin();
+        if sin_theta == 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(u, v) / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+fn rotate_y(v: &Vector3, angle: f64) -> Vector3 {
+    let (sin, cos) = angle.sin_cos();
+    Vector3::new(cos * v.x() + sin * v.z(), v.y(), -sin * v.x() + cos * v.z())
+}
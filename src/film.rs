@@ -0,0 +1,196 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::{aov::AovFilm, color::Color, filter::Filter, image::Image, tonemap::ToneMapping};
+
+// Everything gathered for one pixel so far. sum and weight add up the filter weighted
+// samples splatted into the pixel, from it and its neighbors. For the samples taken in the
+// pixel itself it also keeps a running mean and variance of their brightness (Welford's
+// method), so we can tell how noisy the pixel still is.
+pub struct Pixel {
+    sum: Color,
+    weight: f64,
+    samples: u32,
+    mean: f64,
+    m2: f64,
+}
+
+#[allow(dead_code)]
+impl Pixel {
+    pub fn new() -> Pixel {
+        Pixel {
+            sum: Color::new(0.0, 0.0, 0.0),
+            weight: 0.0,
+            samples: 0,
+            mean: 0.0,
+            m2: 0.0,
+        }
+    }
+    pub fn from_parts(sum: Color, weight: f64, samples: u32, mean: f64, m2: f64) -> Pixel {
+        Pixel {
+            sum,
+            weight,
+            samples,
+            mean,
+            m2,
+        }
+    }
+    pub fn parts(&self) -> (&Color, f64, u32, f64, f64) {
+        (&self.sum, self.weight, self.samples, self.mean, self.m2)
+    }
+    // records a sample taken in this pixel, see Film::splat for adding it to the image
+    pub fn add_sample(&mut self, color: &Color) {
+        let luminance = color.luminance();
+        self.samples += 1;
+        let delta = luminance - self.mean;
+        self.mean += delta / self.samples as f64;
+        self.m2 += delta * (luminance - self.mean);
+    }
+    pub fn add_weighted(&mut self, color: &Color, weight: f64) {
+        self.sum += color.clone() * weight;
+        self.weight += weight;
+    }
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+    pub fn sum(&self) -> &Color {
+        &self.sum
+    }
+    pub fn weight(&self) -> f64 {
+        self.weight
+    }
+    // the pixel's filtered color
+    pub fn color(&self) -> Color {
+        // negative filter lobes can leave a pixel at the edge of a bright area with no
+        // weight to speak of
+        if self.weight > 0.0 {
+            self.sum.clone() * (1.0 / self.weight)
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        }
+    }
+    // variance of the brightness of a single sample
+    pub fn variance(&self) -> f64 {
+        if self.samples < 2 {
+            return 0.0;
+        }
+        self.m2 / (self.samples - 1) as f64
+    }
+    // Estimated error of the pixel's average relative to its brightness. Very dark pixels
+    // are measured against a floor, otherwise their noise would never look small enough.
+    pub fn relative_error(&self) -> f64 {
+        if self.samples < 2 {
+            return f64::INFINITY;
+        }
+        let standard_error = (self.variance() / self.samples as f64).sqrt();
+        standard_error / self.mean.max(0.05)
+    }
+}
+
+// The image being rendered, stored row by row from the top left, along with the aov images
+// of what its camera rays hit if they were asked for.
+pub struct Film {
+    width: i32,
+    height: i32,
+    pixels: Vec<Pixel>,
+    aovs: Option<AovFilm>,
+}
+
+#[allow(dead_code)]
+impl Film {
+    pub fn new(width: i32, height: i32) -> Film {
+        Film {
+            width,
+            height,
+            pixels: (0..width * height).map(|_| Pixel::new()).collect(),
+            aovs: None,
+        }
+    }
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+    pub fn pixel(&self, x: i32, row: i32) -> &Pixel {
+        &self.pixels[(row * self.width + x) as usize]
+    }
+    pub fn pixel_mut(&mut self, x: i32, row: i32) -> &mut Pixel {
+        &mut self.pixels[(row * self.width + x) as usize]
+    }
+
+    // Starts keeping the aov images. They aren't saved in checkpoints, so a resumed film
+    // starts them over and the renderer traces the camera rays of its earlier samples again.
+    pub fn record_aovs(&mut self) {
+        self.aovs = Some(AovFilm::new(self.width, self.height));
+    }
+    pub fn aovs(&self) -> Option<&AovFilm> {
+        self.aovs.as_ref()
+    }
+    pub fn aovs_mut(&mut self) -> Option<&mut AovFilm> {
+        self.aovs.as_mut()
+    }
+
+    // fewest samples any pixel has
+    pub fn min_samples(&self) -> u32 {
+        self.pixels.iter().map(|p| p.samples).min().unwrap_or(0)
+    }
+
+    // Adds a sample that landed at (x, y), measured in pixels from the top left corner of the
+    // image, to every pixel close enough for the filter to reach.
+    pub fn splat(&mut self, x: f64, y: f64, color: &Color, filter: &dyn Filter) {
+        let radius = filter.radius();
+        // pixel centers are at half pixels
+        let x = x - 0.5;
+        let y = y - 0.5;
+        let left = ((x - radius).ceil() as i32).max(0);
+        let right = ((x + radius).floor() as i32).min(self.width - 1);
+        let top = ((y - radius).ceil() as i32).max(0);
+        let bottom = ((y + radius).floor() as i32).min(self.height - 1);
+        for row in top..=bottom {
+            for column in left..=right {
+                let weight = filter.evaluate(x - column as f64, y - row as f64);
+                if weight != 0.0 {
+                    self.pixel_mut(column, row).add_weighted(color, weight);
+                }
+            }
+        }
+    }
+
+    // the filtered color of every pixel
+    pub fn image(&self) -> Image {
+        let pixels = self.pixels.iter().map(|pixel| pixel.color()).collect();
+        Image::new(self.width as usize, self.height as usize, pixels)
+    }
+
+    // How uncertain each pixel's brightness still is, as the variance of its average.
+    pub fn variance(&self) -> Vec<f64> {
+        self.pixels
+            .iter()
+            .map(|pixel| pixel.variance() / pixel.samples.max(1) as f64)
+            .collect()
+    }
+
+    pub fn write_ppm(&self, file_name: &str, tone_mapping: &ToneMapping) {
+        tone_mapping
+            .develop(&self.image())
+            .write_ppm(file_name)
+            .expect("Unable to write image");
+    }
+
+    // Writes how many samples each pixel took as a grayscale image, where white is
+    // max_samples.
+    pub fn write_sample_heatmap(&self, file_name: &str, max_samples: u32) {
+        let f = File::create(file_name).expect("Unable to create file");
+        let mut f = BufWriter::new(f);
+
+        writeln!(f, "P3").expect("unable to write");
+        writeln!(f, "{} {}", self.width, self.height).expect("unable to write");
+        writeln!(f, "255").expect("unable to write");
+        for pixel in &self.pixels {
+            let level = (255.0 * pixel.samples as f64 / max_samples.max(1) as f64).min(255.0);
+            let level = level as i32;
+            writeln!(f, "{level} {level} {level}").expect("unable to write");
+        }
+    }
+}
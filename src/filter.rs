@@ -0,0 +1,296 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::utility::PI;
+
+// How much a sample counts toward a pixel, given how far it landed from the pixel's center
+// in pixels. Each sample is splatted into every pixel whose center is within radius of it,
+// and each pixel ends up as the weighted average of the samples around it. Wider filters
+// give smoother, less aliased edges; filters with negative lobes keep the image sharp.
+pub trait Filter {
+    fn radius(&self) -> f64;
+    fn evaluate(&self, x: f64, y: f64) -> f64;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FilterKind {
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+    Lanczos,
+}
+
+impl FilterKind {
+    pub fn default_radius(&self) -> f64 {
+        match self {
+            FilterKind::Box => 0.5,
+            FilterKind::Tent => 1.0,
+            FilterKind::Gaussian => 1.5,
+            FilterKind::Mitchell => 2.0,
+            FilterKind::Lanczos => 3.0,
+        }
+    }
+}
+
+impl FromStr for FilterKind {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "box" => Ok(FilterKind::Box),
+            "tent" => Ok(FilterKind::Tent),
+            "gaussian" => Ok(FilterKind::Gaussian),
+            "mitchell" => Ok(FilterKind::Mitchell),
+            "lanczos" => Ok(FilterKind::Lanczos),
+            _ => Err(format!("unknown filter {s}")),
+        }
+    }
+}
+
+impl fmt::Display for FilterKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            FilterKind::Box => "box",
+            FilterKind::Tent => "tent",
+            FilterKind::Gaussian => "gaussian",
+            FilterKind::Mitchell => "mitchell",
+            FilterKind::Lanczos => "lanczos",
+        };
+        write!(f, "{name}")
+    }
+}
+
+pub fn create_filter(kind: FilterKind, radius: f64) -> Box<dyn Filter> {
+    match kind {
+        FilterKind::Box => Box::new(BoxFilter::new(radius)),
+        FilterKind::Tent => Box::new(TentFilter::new(radius)),
+        FilterKind::Gaussian => Box::new(GaussianFilter::new(radius)),
+        FilterKind::Mitchell => Box::new(MitchellFilter::new(radius)),
+        FilterKind::Lanczos => Box::new(LanczosFilter::new(radius)),
+    }
+}
+
+// Every sample within the radius counts the same. With the default radius of half a pixel
+// each sample only lands in its own pixel, which is a plain average.
+pub struct BoxFilter {
+    radius: f64,
+}
+
+impl BoxFilter {
+    pub fn new(radius: f64) -> BoxFilter {
+        BoxFilter { radius }
+    }
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        if x.abs() <= self.radius && y.abs() <= self.radius {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+// Falls off linearly to zero at the radius.
+pub struct TentFilter {
+    radius: f64,
+}
+
+impl TentFilter {
+    pub fn new(radius: f64) -> TentFilter {
+        TentFilter { radius }
+    }
+}
+
+impl Filter for TentFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        (self.radius - x.abs()).max(0.0) * (self.radius - y.abs()).max(0.0)
+    }
+}
+
+// A gaussian with a standard deviation of a third of the radius, shifted down so it reaches
+// zero right at the radius instead of being cut off.
+pub struct GaussianFilter {
+    radius: f64,
+    alpha: f64,
+    edge: f64,
+}
+
+impl GaussianFilter {
+    pub fn new(radius: f64) -> GaussianFilter {
+        let sigma = radius / 3.0;
+        let alpha = 1.0 / (2.0 * sigma * sigma);
+        GaussianFilter {
+            radius,
+            alpha,
+            edge: (-alpha * radius * radius).exp(),
+        }
+    }
+    fn gaussian(&self, x: f64) -> f64 {
+        ((-self.alpha * x * x).exp() - self.edge).max(0.0)
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.gaussian(x) * self.gaussian(y)
+    }
+}
+
+// The Mitchell-Netravali cubic with B = C = 1/3, stretched to the radius. Its small negative
+// lobes sharpen edges without much ringing.
+pub struct MitchellFilter {
+    radius: f64,
+}
+
+impl MitchellFilter {
+    pub fn new(radius: f64) -> MitchellFilter {
+        MitchellFilter { radius }
+    }
+    fn mitchell(&self, x: f64) -> f64 {
+        const B: f64 = 1.0 / 3.0;
+        const C: f64 = 1.0 / 3.0;
+        let x = (2.0 * x / self.radius).abs();
+        let value = if x < 1.0 {
+            (12.0 - 9.0 * B - 6.0 * C) * x * x * x
+                + (-18.0 + 12.0 * B + 6.0 * C) * x * x
+                + (6.0 - 2.0 * B)
+        } else if x < 2.0 {
+            (-B - 6.0 * C) * x * x * x
+                + (6.0 * B + 30.0 * C) * x * x
+                + (-12.0 * B - 48.0 * C) * x
+                + (8.0 * B + 24.0 * C)
+        } else {
+            0.0
+        };
+        value / 6.0
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.mitchell(x) * self.mitchell(y)
+    }
+}
+
+// A sinc windowed by a wider sinc that reaches zero at the radius. The sharpest of the
+// filters, but it can ring around very bright edges.
+pub struct LanczosFilter {
+    radius: f64,
+}
+
+impl LanczosFilter {
+    pub fn new(radius: f64) -> LanczosFilter {
+        LanczosFilter { radius }
+    }
+    fn lanczos(&self, x: f64) -> f64 {
+        if x.abs() > self.radius {
+            return 0.0;
+        }
+        sinc(x) * sinc(x / self.radius)
+    }
+}
+
+impl Filter for LanczosFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.lanczos(x) * self.lanczos(y)
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        return 1.0;
+    }
+    (PI * x).sin() / (PI * x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color::Color, film::Film};
+
+    const KINDS: [FilterKind; 5] = [
+        FilterKind::Box,
+        FilterKind::Tent,
+        FilterKind::Gaussian,
+        FilterKind::Mitchell,
+        FilterKind::Lanczos,
+    ];
+
+    #[test]
+    fn filters_peak_at_their_center() {
+        for kind in KINDS {
+            let filter = create_filter(kind, kind.default_radius());
+            let peak = filter.evaluate(0.0, 0.0);
+            assert!(peak > 0.0, "{kind}");
+            let radius = filter.radius();
+            for i in -10..=10 {
+                for j in -10..=10 {
+                    let (x, y) = (radius * i as f64 / 10.0, radius * j as f64 / 10.0);
+                    assert!(filter.evaluate(x, y) <= peak, "{kind} at {x}, {y}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn filters_are_zero_beyond_their_radius() {
+        for kind in KINDS {
+            let filter = create_filter(kind, 1.3);
+            let outside = 1.3 + 1e-9;
+            for (x, y) in [
+                (outside, 0.0),
+                (0.0, outside),
+                (-outside, 0.0),
+                (0.0, -outside),
+                (outside, outside),
+                (2.0, 0.5),
+            ] {
+                assert_eq!(filter.evaluate(x, y), 0.0, "{kind} at {x}, {y}");
+            }
+        }
+    }
+
+    #[test]
+    fn a_half_pixel_box_averages_each_pixel() {
+        let filter = BoxFilter::new(0.5);
+        let mut film = Film::new(3, 2);
+        let mut totals = [0.0; 6];
+        let mut counts = [0; 6];
+        for i in 0..60 {
+            // spread inside the pixels, away from their edges
+            let x = (i % 3) as f64 + 0.05 + 0.9 * ((i * 7) % 11) as f64 / 10.0;
+            let y = ((i / 3) % 2) as f64 + 0.05 + 0.9 * ((i * 5) % 13) as f64 / 12.0;
+            let brightness = (i % 9) as f64 / 8.0;
+            film.splat(x, y, &Color::new(brightness, 0.0, 0.0), &filter);
+            let pixel = (i / 3) % 2 * 3 + i % 3;
+            totals[pixel] += brightness;
+            counts[pixel] += 1;
+        }
+        for row in 0..2 {
+            for x in 0..3 {
+                let pixel = film.pixel(x, row);
+                let i = (row * 3 + x) as usize;
+                assert_eq!(pixel.weight(), counts[i] as f64);
+                let average = pixel.sum().red() / pixel.weight();
+                assert!((average - totals[i] / counts[i] as f64).abs() < 1e-12);
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{
+    camera::{Camera, CameraBasis},
+    ray::Ray,
+    sampler::Sampler,
+    utility::degrees_to_radians,
+};
+
+// How far from the center of a fisheye image a direction lands, given its angle from the
+// view direction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FisheyeMapping {
+    // distance grows evenly with the angle
+    Equidistant,
+    // every part of the image covers the same solid angle, r = 2 sin(angle / 2)
+    Equisolid,
+}
+
+impl FromStr for FisheyeMapping {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "equidistant" => Ok(FisheyeMapping::Equidistant),
+            "equisolid" => Ok(FisheyeMapping::Equisolid),
+            _ => Err(format!("unknown fisheye mapping {s}")),
+        }
+    }
+}
+
+impl fmt::Display for FisheyeMapping {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            FisheyeMapping::Equidistant => "equidistant",
+            FisheyeMapping::Equisolid => "equisolid",
+        };
+        write!(f, "{name}")
+    }
+}
+
+// A circular fisheye lens. The circle fills the height of the image and takes in fov
+// degrees across, up to 360 for everything around the camera; the corners outside it stay
+// black.
+pub struct FisheyeCamera {
+    basis: CameraBasis,
+    half_fov: f64,
+    aspect_ratio: f64,
+    mapping: FisheyeMapping,
+}
+
+#[allow(dead_code)]
+impl FisheyeCamera {
+    pub fn new(
+        basis: CameraBasis,
+        fov: f64,
+        aspect_ratio: f64,
+        mapping: FisheyeMapping,
+    ) -> FisheyeCamera {
+        FisheyeCamera {
+            basis,
+            half_fov: degrees_to_radians(fov.min(360.0)) / 2.0,
+            aspect_ratio,
+            mapping,
+        }
+    }
+
+    // angle from the view direction of a point r from the center, where 1 is the edge
+    fn angle(&self, r: f64) -> f64 {
+        match self.mapping {
+            FisheyeMapping::Equidistant => r * self.half_fov,
+            FisheyeMapping::Equisolid => {
+                2.0 * (r * (self.half_fov / 2.0).sin()).clamp(-1.0, 1.0).asin()
+            }
+        }
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn get_ray(&self, s: f64, t: f64, _sampler: &mut dyn Sampler) -> Option<Ray> {
+        let x = (2.0 * s - 1.0) * self.aspect_ratio;
+        let y = 2.0 * t - 1.0;
+        let r = (x * x + y * y).sqrt();
+        if r > 1.0 {
+            return None;
+        }
+        let theta = self.angle(r);
+        let phi = y.atan2(x);
+        let basis = &self.basis;
+        let direction = (basis.u.clone() * phi.cos() + basis.v.clone() * phi.sin()) * theta.sin()
+            - basis.w.clone() * theta.cos();
+        Some(Ray::new(basis.origin.clone(), direction))
+    }
+}
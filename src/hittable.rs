@@ -4,36 +4,59 @@ use crate::{material::Material, point::Point, ray::Ray, vector3::Vector3};
 
 pub struct HitRecord {
     pub point: Point,
-    pub normal: Vector3,
+    pub normal: Vector3, // shading normal, which normal and bump maps are allowed to change
+    pub geometric_normal: Vector3, // true surface normal, facing the same side as normal
     pub t: f64,
+    pub u: f64,
+    pub v: f64,
+    pub dpdu: Vector3, // how the point moves along the surface as u and v change
+    pub dpdv: Vector3,
     pub front_face: bool,
     pub material: Rc<dyn Material>,
+    pub object_id: usize, // which object of the world was hit, filled in by HittableVec
 }
 
 #[allow(dead_code)]
 impl HitRecord {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         point: Point,
         normal: Vector3,
         t: f64,
+        u: f64,
+        v: f64,
+        dpdu: Vector3,
+        dpdv: Vector3,
         front_face: bool,
         material: Rc<dyn Material>,
     ) -> HitRecord {
         HitRecord {
             point,
+            geometric_normal: normal.clone(),
             normal,
             t,
+            u,
+            v,
+            dpdu,
+            dpdv,
             front_face,
             material,
+            object_id: 0,
         }
     }
     pub fn clone(&self) -> Self {
         HitRecord {
             point: self.point.clone(),
             normal: self.normal.clone(),
+            geometric_normal: self.geometric_normal.clone(),
             t: self.t,
+            u: self.u,
+            v: self.v,
+            dpdu: self.dpdu.clone(),
+            dpdv: self.dpdv.clone(),
             front_face: self.front_face,
             material: Rc::clone(&self.material),
+            object_id: self.object_id,
         }
     }
     pub fn set_face_normal(r: &Ray, outward_normal: &Vector3) -> (Vector3, bool) {
@@ -45,6 +68,11 @@ impl HitRecord {
         };
         (normal, front_face)
     }
+    // True if a direction leaving the hit point goes underneath the actual surface. A bent
+    // shading normal can send reflected rays there, which would leak light through the object.
+    pub fn below_surface(&self, direction: &Vector3) -> bool {
+        Vector3::dot(direction, &self.geometric_normal) <= 0.0
+    }
 }
 
 pub trait Hittable {
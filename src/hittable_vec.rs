@@ -18,15 +18,37 @@ impl HittableVec {
     pub fn push(&mut self, object: Box<dyn Hittable>) {
         self.objects.push(object);
     }
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+    // Swaps the object at index for one built around it, keeping its place in the list.
+    pub fn wrap<F: FnOnce(Box<dyn Hittable>) -> Box<dyn Hittable>>(
+        &mut self,
+        index: usize,
+        wrap: F,
+    ) {
+        let object = std::mem::replace(&mut self.objects[index], Box::new(HittableVec::new()));
+        self.objects[index] = wrap(object);
+    }
 }
+impl Default for HittableVec {
+    fn default() -> Self {
+        HittableVec::new()
+    }
+}
+
 impl Hittable for HittableVec {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
         let mut result: Option<HitRecord> = None;
         let mut closest_so_far = t_max;
 
-        for object in &self.objects {
-            if let Some(x) = object.hit(r, t_min, closest_so_far) {
+        for (id, object) in self.objects.iter().enumerate() {
+            if let Some(mut x) = object.hit(r, t_min, closest_so_far) {
                 closest_so_far = x.t;
+                x.object_id = id;
                 result = Some(x);
             }
         }
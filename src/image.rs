@@ -0,0 +1,356 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Error, ErrorKind, Result, Write};
+
+use crate::{color::Color, region::Window};
+
+// A grid of linear colors, loaded from disk or made by the renderer, stored row by row
+// starting at the top left.
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+#[allow(dead_code)]
+impl Image {
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Image {
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    pub fn pixel(&self, x: usize, y: usize) -> &Color {
+        &self.pixels[y * self.width + x]
+    }
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    // a copy of the pixels inside the window
+    pub fn crop(&self, window: &Window) -> Image {
+        let pixels = (window.y0..window.y1)
+            .flat_map(|row| (window.x0..window.x1).map(move |column| (column, row)))
+            .map(|(column, row)| self.pixel(column as usize, row as usize).clone())
+            .collect();
+        Image::new(window.width() as usize, window.height() as usize, pixels)
+    }
+
+    // Copies the pixels inside the window from other, an image of the same size, over
+    // this one.
+    pub fn paste(&mut self, other: &Image, window: &Window) {
+        for row in window.y0 as usize..window.y1 as usize {
+            for column in window.x0 as usize..window.x1 as usize {
+                self.pixels[row * self.width + column] = other.pixel(column, row).clone();
+            }
+        }
+    }
+
+    // Loads an image picking the format from the file extension.
+    pub fn load(file_name: &str) -> Result<Image> {
+        let lower = file_name.to_lowercase();
+        if lower.ends_with(".hdr") {
+            Image::load_hdr(file_name)
+        } else if lower.ends_with(".pfm") {
+            Image::load_pfm(file_name)
+        } else {
+            Image::load_ppm(file_name)
+        }
+    }
+
+    // Reads a binary (P6) or plain text (P3) ppm file. Channels are scaled to 0..1 but are
+    // otherwise left alone, so data like normal maps comes through unchanged.
+    pub fn load_ppm(file_name: &str) -> Result<Image> {
+        let bytes = fs::read(file_name)?;
+        let mut pos = 0;
+        let magic = next_token(&bytes, &mut pos)?;
+        let width: usize = parse_token(&bytes, &mut pos)?;
+        let height: usize = parse_token(&bytes, &mut pos)?;
+        let max_value: f64 = parse_token(&bytes, &mut pos)?;
+        // textures are looked up by scaling texture coordinates to the size, which needs at
+        // least one pixel
+        if width == 0 || height == 0 {
+            return Err(invalid_data("ppm file has no pixels"));
+        }
+
+        let count = width * height * 3;
+        let channels: Vec<f64> = match magic.as_str() {
+            "P3" => (0..count)
+                .map(|_| parse_token::<f64>(&bytes, &mut pos))
+                .collect::<Result<_>>()?,
+            "P6" => {
+                // exactly one whitespace byte separates the header from the pixel data
+                let data = &bytes[(pos + 1).min(bytes.len())..];
+                if max_value < 256.0 {
+                    data.iter().take(count).map(|&b| b as f64).collect()
+                } else {
+                    data.chunks_exact(2)
+                        .take(count)
+                        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]) as f64)
+                        .collect()
+                }
+            }
+            _ => return Err(invalid_data("not a P3 or P6 ppm file")),
+        };
+        if channels.len() < count {
+            return Err(invalid_data("ppm file ended early"));
+        }
+
+        let pixels = channels
+            .chunks_exact(3)
+            .map(|c| Color::new(c[0] / max_value, c[1] / max_value, c[2] / max_value))
+            .collect();
+        Ok(Image::new(width, height, pixels))
+    }
+
+    // Reads a Radiance rgbe (.hdr) file, either run length encoded or flat.
+    pub fn load_hdr(file_name: &str) -> Result<Image> {
+        let bytes = fs::read(file_name)?;
+        let mut pos = 0;
+        let mut format_ok = true;
+        // the header is a list of lines ending with a blank one
+        loop {
+            let line = next_line(&bytes, &mut pos)?;
+            if line.is_empty() {
+                break;
+            }
+            if let Some(format) = line.strip_prefix("FORMAT=") {
+                format_ok = format == "32-bit_rle_rgbe";
+            }
+        }
+        if !format_ok {
+            return Err(invalid_data("only 32-bit_rle_rgbe hdr files are supported"));
+        }
+        let resolution = next_line(&bytes, &mut pos)?;
+        let parts: Vec<&str> = resolution.split_whitespace().collect();
+        if parts.len() != 4 || parts[0] != "-Y" || parts[2] != "+X" {
+            return Err(invalid_data("unsupported hdr orientation"));
+        }
+        let height: usize = parts[1].parse().map_err(|_| invalid_data("bad hdr height"))?;
+        let width: usize = parts[3].parse().map_err(|_| invalid_data("bad hdr width"))?;
+
+        let mut pixels = Vec::with_capacity(width * height);
+        let mut scanline = vec![0u8; width * 4];
+        for _ in 0..height {
+            read_hdr_scanline(&bytes, &mut pos, &mut scanline)?;
+            for rgbe in scanline.chunks_exact(4) {
+                pixels.push(rgbe_to_color(rgbe));
+            }
+        }
+        Ok(Image::new(width, height, pixels))
+    }
+
+    // Reads a portable float map, color (PF) or grayscale (Pf).
+    pub fn load_pfm(file_name: &str) -> Result<Image> {
+        let bytes = fs::read(file_name)?;
+        let mut pos = 0;
+        let channels = match next_token(&bytes, &mut pos)?.as_str() {
+            "PF" => 3,
+            "Pf" => 1,
+            _ => return Err(invalid_data("not a PF or Pf pfm file")),
+        };
+        let width: usize = parse_token(&bytes, &mut pos)?;
+        let height: usize = parse_token(&bytes, &mut pos)?;
+        // a negative scale means the floats are little endian
+        let scale: f64 = parse_token(&bytes, &mut pos)?;
+        let data = &bytes[(pos + 1).min(bytes.len())..];
+        if data.len() < width * height * channels * 4 {
+            return Err(invalid_data("pfm file ended early"));
+        }
+
+        let floats: Vec<f64> = data
+            .chunks_exact(4)
+            .take(width * height * channels)
+            .map(|b| {
+                let b = [b[0], b[1], b[2], b[3]];
+                if scale < 0.0 {
+                    f32::from_le_bytes(b) as f64
+                } else {
+                    f32::from_be_bytes(b) as f64
+                }
+            })
+            .collect();
+
+        // rows are stored from the bottom of the image up
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in (0..height).rev() {
+            for x in 0..width {
+                let i = (y * width + x) * channels;
+                pixels.push(if channels == 3 {
+                    Color::new(floats[i], floats[i + 1], floats[i + 2])
+                } else {
+                    Color::new(floats[i], floats[i], floats[i])
+                });
+            }
+        }
+        Ok(Image::new(width, height, pixels))
+    }
+
+    // Writes the colors as they are, clamped to between 0 and 1, to an 8 bit ppm file.
+    pub fn write_ppm(&self, file_name: &str) -> Result<()> {
+        let mut f = BufWriter::new(File::create(file_name)?);
+        writeln!(f, "P3")?;
+        writeln!(f, "{} {}", self.width, self.height)?;
+        writeln!(f, "255")?;
+        for color in &self.pixels {
+            let byte = |c: f64| (256.0 * c.clamp(0.0, 0.999)) as i32;
+            writeln!(
+                f,
+                "{} {} {}",
+                byte(color.red()),
+                byte(color.green()),
+                byte(color.blue())
+            )?;
+        }
+        f.flush()
+    }
+
+    // Writes the colors unchanged to a little endian color pfm file.
+    pub fn write_pfm(&self, file_name: &str) -> Result<()> {
+        let mut f = BufWriter::new(File::create(file_name)?);
+        write!(f, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+        // rows are stored from the bottom of the image up
+        for y in (0..self.height).rev() {
+            for x in 0..self.width {
+                let color = self.pixel(x, y);
+                for value in [color.red(), color.green(), color.blue()] {
+                    f.write_all(&(value as f32).to_le_bytes())?;
+                }
+            }
+        }
+        f.flush()
+    }
+}
+
+fn read_hdr_scanline(bytes: &[u8], pos: &mut usize, scanline: &mut [u8]) -> Result<()> {
+    let width = scanline.len() / 4;
+    let byte = |i: usize| -> Result<u8> {
+        bytes
+            .get(i)
+            .copied()
+            .ok_or_else(|| invalid_data("hdr file ended early"))
+    };
+
+    let run_length_encoded = (8..0x8000).contains(&width)
+        && byte(*pos)? == 2
+        && byte(*pos + 1)? == 2
+        && byte(*pos + 2)? & 0x80 == 0;
+    if !run_length_encoded {
+        for value in scanline.iter_mut() {
+            *value = byte(*pos)?;
+            *pos += 1;
+        }
+        return Ok(());
+    }
+    let encoded_width = ((byte(*pos + 2)? as usize) << 8) | byte(*pos + 3)? as usize;
+    if encoded_width != width {
+        return Err(invalid_data("hdr scanline width mismatch"));
+    }
+    *pos += 4;
+
+    // each of the four channels is stored separately as a mix of runs and literal bytes
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let count = byte(*pos)? as usize;
+            *pos += 1;
+            if count > 128 {
+                let value = byte(*pos)?;
+                *pos += 1;
+                for _ in 0..(count - 128) {
+                    if x >= width {
+                        return Err(invalid_data("hdr run overflows scanline"));
+                    }
+                    scanline[x * 4 + channel] = value;
+                    x += 1;
+                }
+            } else {
+                for _ in 0..count {
+                    if x >= width {
+                        return Err(invalid_data("hdr run overflows scanline"));
+                    }
+                    scanline[x * 4 + channel] = byte(*pos)?;
+                    *pos += 1;
+                    x += 1;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn rgbe_to_color(rgbe: &[u8]) -> Color {
+    if rgbe[3] == 0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    let f = 2f64.powi(rgbe[3] as i32 - 136);
+    Color::new(rgbe[0] as f64 * f, rgbe[1] as f64 * f, rgbe[2] as f64 * f)
+}
+
+pub fn next_line(bytes: &[u8], pos: &mut usize) -> Result<String> {
+    if *pos >= bytes.len() {
+        return Err(invalid_data("unexpected end of file"));
+    }
+    let start = *pos;
+    while *pos < bytes.len() && bytes[*pos] != b'\n' {
+        *pos += 1;
+    }
+    let line = String::from_utf8_lossy(&bytes[start..*pos]).trim().to_string();
+    *pos += 1;
+    Ok(line)
+}
+
+pub fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+// Returns the next whitespace separated word of a text header, skipping # comments.
+pub fn next_token(bytes: &[u8], pos: &mut usize) -> Result<String> {
+    loop {
+        while *pos < bytes.len() && bytes[*pos].is_ascii_whitespace() {
+            *pos += 1;
+        }
+        if *pos < bytes.len() && bytes[*pos] == b'#' {
+            while *pos < bytes.len() && bytes[*pos] != b'\n' {
+                *pos += 1;
+            }
+            continue;
+        }
+        break;
+    }
+    let start = *pos;
+    while *pos < bytes.len() && !bytes[*pos].is_ascii_whitespace() {
+        *pos += 1;
+    }
+    if start == *pos {
+        return Err(invalid_data("unexpected end of file"));
+    }
+    Ok(String::from_utf8_lossy(&bytes[start..*pos]).into_owned())
+}
+
+pub fn parse_token<T: std::str::FromStr>(bytes: &[u8], pos: &mut usize) -> Result<T> {
+    next_token(bytes, pos)?
+        .parse()
+        .map_err(|_| invalid_data("malformed number in header"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_ppm_files_are_rejected() {
+        let file_name = std::env::temp_dir().join(format!("empty-{}.ppm", std::process::id()));
+        let file_name = file_name.to_str().unwrap();
+        fs::write(file_name, "P3\n0 0\n255\n").unwrap();
+        let result = Image::load_ppm(file_name);
+        fs::remove_file(file_name).unwrap();
+        assert_eq!(result.err().map(|e| e.kind()), Some(ErrorKind::InvalidData));
+    }
+}
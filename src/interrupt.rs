@@ -0,0 +1,34 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// SIGINT is 2 on every platform we build for
+const SIGINT: i32 = 2;
+// what signal returns when it can't install the handler, (void (*)(int)) -1
+const SIG_ERR: usize = usize::MAX;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" {
+    fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+}
+
+extern "C" fn on_interrupt(_signum: i32) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+// Makes Ctrl-C set a flag instead of killing the program, so a render can save its progress
+// before exiting.
+pub fn catch_interrupt() {
+    // SAFETY: signal is the C library's own, declared with its real signature: an int and a
+    // handler in, the previous handler, a pointer sized value, out. The handler only stores
+    // to an atomic, which is one of the few things safe to do inside a signal handler.
+    let previous = unsafe { signal(SIGINT, on_interrupt) };
+    if previous == SIG_ERR {
+        eprintln!(
+            "Unable to catch Ctrl-C, interrupting will lose progress since the last checkpoint"
+        );
+    }
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
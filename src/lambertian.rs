@@ -3,6 +3,8 @@ use crate::{
     hittable::HitRecord,
     material::{Material, ScatterResult},
     ray::Ray,
+    sampler::Sampler,
+    utility::PI,
     vector3::Vector3,
 };
 
@@ -18,17 +20,30 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _r_in: &Ray, rec: &HitRecord) -> ScatterResult {
-        let mut scatter_direction = rec.normal.clone() + Vector3::random_unit_vector();
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> ScatterResult {
+        let mut scatter_direction =
+            rec.normal.clone() + Vector3::sample_unit_vector(sampler.get_2d());
         if scatter_direction.near_zero() {
             scatter_direction = rec.normal.clone()
         }
+        let success = !rec.below_surface(&scatter_direction);
         let scattered = Ray::new(rec.point.clone(), scatter_direction);
         let attenuation = self.albedo.clone();
         ScatterResult {
-            success: true,
+            success,
             attenuation,
             scattered,
         }
     }
+
+    fn eval(&self, _r_in: &Ray, rec: &HitRecord, direction: &Vector3) -> Option<(Color, f64)> {
+        let cosine = Vector3::dot(&Vector3::unit_vector(direction), &rec.normal);
+        if cosine <= 0.0 || rec.below_surface(direction) {
+            return Some((Color::new(0.0, 0.0, 0.0), 0.0));
+        }
+        Some((self.albedo.clone() * (cosine / PI), cosine / PI))
+    }
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        self.albedo.clone()
+    }
 }
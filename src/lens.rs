@@ -0,0 +1,562 @@
+use std::io::Result;
+
+use crate::{
+    camera::{Camera, CameraBasis},
+    image::invalid_data,
+    point::Point,
+    ray::Ray,
+    sampler::Sampler,
+    vector3::Vector3,
+};
+
+// how many bands of distance from the center of the film the exit pupil is worked out for,
+// the film points tried in each band and the points tried across the back of the lens each
+// way
+const PUPIL_BANDS: usize = 32;
+const PUPIL_FILM_STEPS: usize = 4;
+const PUPIL_LENS_STEPS: usize = 64;
+
+// One surface of a lens, in meters. A curvature radius of 0 marks the aperture stop, and
+// eta is the index of refraction of what lies behind the surface, toward the film.
+struct LensSurface {
+    curvature_radius: f64,
+    thickness: f64,
+    eta: f64,
+    aperture_radius: f64,
+}
+
+impl LensSurface {
+    fn is_stop(&self) -> bool {
+        self.curvature_radius == 0.0
+    }
+    // the stop has no glass, it's a hole in the air
+    fn eta(&self) -> f64 {
+        if self.eta == 0.0 {
+            1.0
+        } else {
+            self.eta
+        }
+    }
+
+    // Where a ray meets the surface with its vertex at z on the axis, as the distance along
+    // the ray and the surface normal facing back along the ray.
+    fn intersect(&self, z: f64, ray: &Ray) -> Option<(f64, Vector3)> {
+        let direction = ray.direction();
+        if self.is_stop() {
+            let t = (z - ray.origin().z()) / direction.z();
+            return Some((t, Vector3::new(0.0, 0.0, -direction.z().signum())));
+        }
+        let radius = self.curvature_radius;
+        let oc = ray.origin().clone() - Point::new(0.0, 0.0, z + radius);
+        let a = direction.length_squared();
+        let half_b = Vector3::dot(&oc, direction);
+        let c = oc.length_squared() - radius * radius;
+        let discriminant = half_b * half_b - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        let (near, far) = ((-half_b - root) / a, (-half_b + root) / a);
+        // the part of the sphere near the vertex is the first one met when the ray travels
+        // from the side the sphere bulges out of
+        let t = if (direction.z() > 0.0) != (radius < 0.0) {
+            near
+        } else {
+            far
+        };
+        if t < 0.0 {
+            return None;
+        }
+        let normal = Vector3::unit_vector(&(oc + direction.clone() * t));
+        if Vector3::dot(&normal, direction) > 0.0 {
+            Some((t, -normal))
+        } else {
+            Some((t, normal))
+        }
+    }
+
+    // Carries the ray on to the surface and through it, or None when the edge of the glass
+    // or the stop blocks it or it's reflected back inside.
+    fn pass(&self, z: f64, ray: &Ray, eta_ratio: f64) -> Option<Ray> {
+        let (t, normal) = self.intersect(z, ray)?;
+        let hit = ray.at(t);
+        if hit.x() * hit.x() + hit.y() * hit.y() > self.aperture_radius * self.aperture_radius {
+            return None;
+        }
+        if self.is_stop() {
+            return Some(Ray::new(hit, ray.direction().clone()));
+        }
+        let direction = Vector3::unit_vector(ray.direction());
+        let cos_theta = Vector3::dot(&-direction.clone(), &normal);
+        if eta_ratio * eta_ratio * (1.0 - cos_theta * cos_theta) > 1.0 {
+            return None;
+        }
+        Some(Ray::new(
+            hit,
+            Vector3::refract(&direction, &normal, eta_ratio),
+        ))
+    }
+}
+
+// A rectangle on the plane of the back of the lens, in meters.
+#[derive(Clone, Copy, Debug)]
+struct PupilBounds {
+    min_x: f64,
+    min_y: f64,
+    max_x: f64,
+    max_y: f64,
+}
+
+impl PupilBounds {
+    fn point(x: f64, y: f64) -> PupilBounds {
+        PupilBounds {
+            min_x: x,
+            min_y: y,
+            max_x: x,
+            max_y: y,
+        }
+    }
+    fn contains(&self, x: f64, y: f64) -> bool {
+        self.min_x <= x && x <= self.max_x && self.min_y <= y && y <= self.max_y
+    }
+    fn extend(&mut self, x: f64, y: f64) {
+        self.min_x = self.min_x.min(x);
+        self.min_y = self.min_y.min(y);
+        self.max_x = self.max_x.max(x);
+        self.max_y = self.max_y.max(y);
+    }
+    fn grow(self, margin: f64) -> PupilBounds {
+        PupilBounds {
+            min_x: self.min_x - margin,
+            min_y: self.min_y - margin,
+            max_x: self.max_x + margin,
+            max_y: self.max_y + margin,
+        }
+    }
+    fn area(&self) -> f64 {
+        (self.max_x - self.min_x) * (self.max_y - self.min_y)
+    }
+    fn sample(&self, (u1, u2): (f64, f64)) -> (f64, f64) {
+        (
+            self.min_x + u1 * (self.max_x - self.min_x),
+            self.min_y + u2 * (self.max_y - self.min_y),
+        )
+    }
+}
+
+// The stack of glass elements in a real lens, read from a prescription like those in lens
+// patents. The film sits at z = 0 and the lens in front of it toward negative z.
+pub struct LensSystem {
+    surfaces: Vec<LensSurface>,
+}
+
+#[allow(dead_code)]
+impl LensSystem {
+    // Reads a prescription with one surface per line from the front of the lens to the
+    // back, each given as its radius of curvature, the distance to the next surface, the
+    // index of refraction of the glass up to it and the diameter of its opening, all in
+    // millimeters. The aperture stop has a radius and index of 0. Lines starting with # are
+    // comments.
+    pub fn load(file_name: &str) -> Result<LensSystem> {
+        let text = std::fs::read_to_string(file_name)?;
+        let mut surfaces = Vec::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let values: Vec<f64> = line
+                .split_whitespace()
+                .map(|value| value.parse())
+                .collect::<std::result::Result<_, _>>()
+                .map_err(|_| invalid_data(&format!("invalid lens surface {line}")))?;
+            if values.len() != 4 {
+                return Err(invalid_data(&format!(
+                    "a lens surface needs 4 values, not {line}"
+                )));
+            }
+            surfaces.push(LensSurface {
+                curvature_radius: values[0] / 1000.0,
+                thickness: values[1] / 1000.0,
+                eta: values[2],
+                aperture_radius: values[3] / 2000.0,
+            });
+        }
+        if surfaces.is_empty() {
+            return Err(invalid_data("the lens has no surfaces"));
+        }
+        Ok(LensSystem { surfaces })
+    }
+
+    // Stops the lens down to an opening of this diameter in millimeters. It can't open any
+    // wider than the prescription allows.
+    pub fn with_stop_diameter(mut self, diameter: f64) -> Result<Self> {
+        let stop = self
+            .surfaces
+            .iter_mut()
+            .find(|surface| surface.is_stop())
+            .ok_or_else(|| invalid_data("the lens has no aperture stop"))?;
+        stop.aperture_radius = stop.aperture_radius.min(diameter / 2000.0);
+        Ok(self)
+    }
+
+    fn rear_z(&self) -> f64 {
+        -self.surfaces[self.surfaces.len() - 1].thickness
+    }
+    fn front_z(&self) -> f64 {
+        -self
+            .surfaces
+            .iter()
+            .map(|surface| surface.thickness)
+            .sum::<f64>()
+    }
+    fn rear_aperture_radius(&self) -> f64 {
+        self.surfaces[self.surfaces.len() - 1].aperture_radius
+    }
+
+    // Follows a ray leaving the film out through the lens into the scene.
+    fn trace_from_film(&self, ray: &Ray) -> Option<Ray> {
+        let mut ray = Ray::new(ray.origin().clone(), ray.direction().clone());
+        let mut z = 0.0;
+        for (i, surface) in self.surfaces.iter().enumerate().rev() {
+            z -= surface.thickness;
+            let eta_in_front = if i > 0 {
+                self.surfaces[i - 1].eta()
+            } else {
+                1.0
+            };
+            ray = surface.pass(z, &ray, surface.eta() / eta_in_front)?;
+        }
+        Some(ray)
+    }
+
+    // Follows a ray coming from the scene in through the lens toward the film.
+    fn trace_from_scene(&self, ray: &Ray) -> Option<Ray> {
+        let mut ray = Ray::new(ray.origin().clone(), ray.direction().clone());
+        let mut z = self.front_z();
+        for (i, surface) in self.surfaces.iter().enumerate() {
+            let eta_in_front = if i > 0 {
+                self.surfaces[i - 1].eta()
+            } else {
+                1.0
+            };
+            ray = surface.pass(z, &ray, eta_in_front / surface.eta())?;
+            z += surface.thickness;
+        }
+        Some(ray)
+    }
+
+    // Moves the lens toward or away from the film until things distance meters in front of
+    // the film are sharp. The whole lens is treated as a thick lens, found by sending rays
+    // close to the axis through it both ways.
+    pub fn focus(&mut self, distance: f64) -> Result<()> {
+        let height = self
+            .surfaces
+            .iter()
+            .map(|surface| surface.aperture_radius)
+            .fold(f64::INFINITY, f64::min)
+            / 1000.0;
+        let from_scene = Ray::new(
+            Point::new(height, 0.0, self.front_z() - 1.0),
+            Vector3::new(0.0, 0.0, 1.0),
+        );
+        let from_film = Ray::new(Point::new(height, 0.0, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        let (rear_principal, rear_focal) = self
+            .trace_from_scene(&from_scene)
+            .map(|out| cardinal_points(&from_scene, &out))
+            .ok_or_else(|| invalid_data("light along the axis doesn't make it through the lens"))?;
+        let (front_principal, _) = self
+            .trace_from_film(&from_film)
+            .map(|out| cardinal_points(&from_film, &out))
+            .ok_or_else(|| invalid_data("light along the axis doesn't make it through the lens"))?;
+        let focal_length = rear_focal - rear_principal;
+        if focal_length.is_nan() || focal_length <= 0.0 {
+            return Err(invalid_data("the lens doesn't bring light to a focus"));
+        }
+
+        // 1 / object distance + 1 / image distance = 1 / focal length, solved for how far
+        // the film has to move back
+        let a = distance + front_principal;
+        let b = -rear_principal;
+        let discriminant = (a + b) * (a + b - 4.0 * focal_length);
+        if discriminant <= 0.0 {
+            return Err(invalid_data("the focus distance is too close for the lens"));
+        }
+        let delta = ((a - b) - discriminant.sqrt()) / 2.0;
+        let last = self.surfaces.len() - 1;
+        self.surfaces[last].thickness += delta;
+        if self.surfaces[last].thickness < 0.0 {
+            return Err(invalid_data("the lens can't focus that far away"));
+        }
+        Ok(())
+    }
+
+    // The points across the back of the lens, each way, that exit pupils are searched over,
+    // and the distance between them.
+    fn pupil_grid(&self) -> impl Iterator<Item = (f64, f64)> {
+        let (extent, step) = self.pupil_grid_extent();
+        (0..PUPIL_LENS_STEPS * PUPIL_LENS_STEPS).map(move |j| {
+            (
+                -extent + step * ((j % PUPIL_LENS_STEPS) as f64 + 0.5),
+                -extent + step * ((j / PUPIL_LENS_STEPS) as f64 + 0.5),
+            )
+        })
+    }
+    fn pupil_grid_extent(&self) -> (f64, f64) {
+        let extent = 1.5 * self.rear_aperture_radius();
+        (extent, 2.0 * extent / PUPIL_LENS_STEPS as f64)
+    }
+
+    // A rectangle on the back of the lens that every ray making it through from a film point
+    // on the x axis between near and far from the center passes through, or None if no
+    // light gets through to there at all.
+    fn exit_pupil_bounds(&self, near: f64, far: f64) -> Option<PupilBounds> {
+        let mut bounds: Option<PupilBounds> = None;
+        for i in 0..PUPIL_FILM_STEPS {
+            let x = near + (far - near) * i as f64 / (PUPIL_FILM_STEPS - 1) as f64;
+            let film = Point::new(x, 0.0, 0.0);
+            for (x, y) in self.pupil_grid() {
+                // points inside what's already found can't make it any bigger
+                if bounds.is_some_and(|bounds| bounds.contains(x, y)) {
+                    continue;
+                }
+                let ray = Ray::new(film.clone(), Point::new(x, y, self.rear_z()) - film.clone());
+                if self.trace_from_film(&ray).is_some() {
+                    match bounds.as_mut() {
+                        Some(bounds) => bounds.extend(x, y),
+                        None => bounds = Some(PupilBounds::point(x, y)),
+                    }
+                }
+            }
+        }
+        // the grid only finds the pupil to within a cell
+        let (_, step) = self.pupil_grid_extent();
+        bounds.map(|bounds| bounds.grow(step))
+    }
+
+    // How much light from an evenly bright scene reaches the center of the film, as the sum
+    // of cos^4 / distance^2 over the back of the lens where it gets through. Ray weights are
+    // measured against this.
+    fn center_irradiance(&self) -> f64 {
+        let (_, step) = self.pupil_grid_extent();
+        let film = Point::origin();
+        let distance = -self.rear_z();
+        self.pupil_grid()
+            .map(|(x, y)| {
+                let ray = Ray::new(film.clone(), Point::new(x, y, self.rear_z()) - film.clone());
+                if self.trace_from_film(&ray).is_none() {
+                    return 0.0;
+                }
+                let cos_theta = distance / ray.direction().length();
+                cos_theta.powi(4) / (distance * distance) * step * step
+            })
+            .sum()
+    }
+}
+
+// Where the principal plane and focal point of one side of a thick lens are along the axis,
+// from a ray parallel to the axis going in and what comes out the other side.
+fn cardinal_points(input: &Ray, output: &Ray) -> (f64, f64) {
+    let origin = output.origin();
+    let direction = output.direction();
+    let to_focal = -origin.x() / direction.x();
+    let to_principal = (input.origin().x() - origin.x()) / direction.x();
+    (
+        origin.z() + to_principal * direction.z(),
+        origin.z() + to_focal * direction.z(),
+    )
+}
+
+// A camera that traces its rays from the film through every element of a real lens, so the
+// picture gets the lens's own distortion, falloff toward the corners and out of focus
+// highlights. Rays are aimed at where light can leave the lens from each part of the film,
+// as pbrt does, and weighted by how much light they stand for, so the middle of the frame is
+// as bright as with the thin lens camera and only what the lens really blocks darkens the
+// rest.
+pub struct RealisticCamera {
+    basis: CameraBasis,
+    lens: LensSystem,
+    film_width: f64,
+    film_height: f64,
+    half_diagonal: f64,
+    // for each band of distance from the center of the film, found along the x axis
+    pupil_bounds: Vec<Option<PupilBounds>>,
+    center_irradiance: f64,
+}
+
+#[allow(dead_code)]
+impl RealisticCamera {
+    // film_width is in millimeters and focus_distance in meters from the film.
+    pub fn new(
+        basis: CameraBasis,
+        mut lens: LensSystem,
+        film_width: f64,
+        aspect_ratio: f64,
+        focus_distance: f64,
+    ) -> Result<RealisticCamera> {
+        lens.focus(focus_distance)?;
+        let film_width = film_width / 1000.0;
+        let film_height = film_width / aspect_ratio;
+        let half_diagonal = (film_width * film_width + film_height * film_height).sqrt() / 2.0;
+        let pupil_bounds = (0..PUPIL_BANDS)
+            .map(|band| {
+                let near = half_diagonal * band as f64 / PUPIL_BANDS as f64;
+                let far = half_diagonal * (band + 1) as f64 / PUPIL_BANDS as f64;
+                lens.exit_pupil_bounds(near, far)
+            })
+            .collect();
+        let center_irradiance = lens.center_irradiance();
+        if center_irradiance == 0.0 {
+            return Err(invalid_data(
+                "no light makes it through the lens to the middle of the film",
+            ));
+        }
+        Ok(RealisticCamera {
+            basis,
+            lens,
+            film_width,
+            film_height,
+            half_diagonal,
+            pupil_bounds,
+            center_irradiance,
+        })
+    }
+}
+
+impl Camera for RealisticCamera {
+    fn get_ray(&self, s: f64, t: f64, sampler: &mut dyn Sampler) -> Option<Ray> {
+        self.get_weighted_ray(s, t, sampler).map(|(r, _)| r)
+    }
+
+    fn get_weighted_ray(&self, s: f64, t: f64, sampler: &mut dyn Sampler) -> Option<(Ray, f64)> {
+        // the lens turns the picture upside down on the film
+        let film = Point::new(
+            (0.5 - s) * self.film_width,
+            (0.5 - t) * self.film_height,
+            0.0,
+        );
+        let radius = (film.x() * film.x() + film.y() * film.y()).sqrt();
+        let band =
+            ((radius / self.half_diagonal * PUPIL_BANDS as f64) as usize).min(PUPIL_BANDS - 1);
+        let bounds = self.pupil_bounds[band].as_ref()?;
+        let (x, y) = bounds.sample(sampler.get_2d());
+        // the bounds were found for film points along the x axis, so turn the point on the
+        // lens around to face this one
+        let (sin_phi, cos_phi) = if radius > 0.0 {
+            (film.y() / radius, film.x() / radius)
+        } else {
+            (0.0, 1.0)
+        };
+        let target = Point::new(
+            x * cos_phi - y * sin_phi,
+            x * sin_phi + y * cos_phi,
+            self.lens.rear_z(),
+        );
+        let ray = Ray::new(film.clone(), target - film);
+        // the light arriving through the part of the lens sampled, cos^4 / distance^2 over
+        // its area, compared with what reaches the center of the film through all of it
+        let distance = -self.lens.rear_z();
+        let cos_theta = distance / ray.direction().length();
+        let weight =
+            cos_theta.powi(4) / (distance * distance) * bounds.area() / self.center_irradiance;
+        let ray = self.lens.trace_from_film(&ray)?;
+
+        let basis = &self.basis;
+        let to_world = |x: f64, y: f64, z: f64| {
+            basis.u.clone() * x + basis.v.clone() * y + basis.w.clone() * z
+        };
+        let (origin, direction) = (ray.origin(), ray.direction());
+        Some((
+            Ray::new(
+                basis.origin.clone() + to_world(origin.x(), origin.y(), origin.z()),
+                to_world(direction.x(), direction.y(), direction.z()),
+            ),
+            weight,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundled_lens() -> LensSystem {
+        let file_name = concat!(env!("CARGO_MANIFEST_DIR"), "/lenses/double-gauss-50mm.txt");
+        LensSystem::load(file_name).unwrap()
+    }
+
+    // writes a prescription to a file of its own and loads it
+    fn load_text(name: &str, text: &str) -> Result<LensSystem> {
+        let path = std::env::temp_dir().join(format!("{name}-{}.txt", std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        let lens = LensSystem::load(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        lens
+    }
+
+    #[test]
+    fn reads_a_prescription_in_millimeters() {
+        let lens = load_text(
+            "lens-parse",
+            "# a comment\n\n  50 4 1.5 20\n0 2 0 10\n-50 30 1 20\n",
+        )
+        .unwrap();
+        assert_eq!(lens.surfaces.len(), 3);
+        let front = &lens.surfaces[0];
+        assert_eq!(front.curvature_radius, 0.05);
+        assert_eq!(front.thickness, 0.004);
+        assert_eq!(front.eta(), 1.5);
+        assert_eq!(front.aperture_radius, 0.01);
+        assert!(lens.surfaces[1].is_stop());
+        assert_eq!(lens.surfaces[1].eta(), 1.0);
+        assert!((lens.front_z() + 0.036).abs() < 1e-12);
+        assert!((lens.rear_z() + 0.03).abs() < 1e-12);
+    }
+
+    #[test]
+    fn rejects_bad_prescriptions() {
+        assert!(load_text("lens-short", "50 4 1.5\n").is_err());
+        assert!(load_text("lens-word", "50 4 glass 20\n").is_err());
+        assert!(load_text("lens-empty", "# nothing here\n").is_err());
+    }
+
+    #[test]
+    fn stopping_down_never_opens_the_lens_up() {
+        let lens = bundled_lens().with_stop_diameter(8.0).unwrap();
+        let stop = lens.surfaces.iter().find(|s| s.is_stop()).unwrap();
+        assert_eq!(stop.aperture_radius, 0.004);
+        let lens = bundled_lens().with_stop_diameter(100.0).unwrap();
+        let stop = lens.surfaces.iter().find(|s| s.is_stop()).unwrap();
+        assert_eq!(stop.aperture_radius, 17.1 / 2000.0);
+    }
+
+    #[test]
+    fn focusing_at_infinity_puts_the_film_at_the_focal_length() {
+        let mut lens = bundled_lens();
+        lens.focus(1e9).unwrap();
+        // light from far away along the axis comes to a point on the film, about 50mm
+        // behind the rear principal plane
+        let height = 0.001;
+        let from_scene = Ray::new(
+            Point::new(height, 0.0, lens.front_z() - 1.0),
+            Vector3::new(0.0, 0.0, 1.0),
+        );
+        let out = lens.trace_from_scene(&from_scene).unwrap();
+        let (principal, focal) = cardinal_points(&from_scene, &out);
+        assert!(focal.abs() < 1e-5, "focused {focal} m from the film");
+        let focal_length = focal - principal;
+        assert!(
+            (focal_length - 0.05).abs() < 0.001,
+            "focal length {focal_length} m"
+        );
+    }
+
+    #[test]
+    fn focusing_closer_moves_the_lens_out() {
+        let mut far = bundled_lens();
+        far.focus(1e9).unwrap();
+        let mut near = bundled_lens();
+        near.focus(1.0).unwrap();
+        assert!(near.front_z() < far.front_z());
+        assert!(bundled_lens().focus(0.01).is_err());
+    }
+}
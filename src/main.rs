@@ -1,30 +1,78 @@
+mod alpha_mask;
+mod animation;
+mod aov;
+mod aperture;
+mod background;
 mod camera;
+mod checkpoint;
+mod coated;
 mod color;
+mod denoise;
 mod dielectric;
+mod distribution;
+mod environment;
+mod film;
+mod filter;
+mod fisheye;
 mod hittable;
 pub mod hittable_vec;
+mod image;
+mod interrupt;
 mod lambertian;
+mod lens;
 mod material;
 mod metal;
+mod normal_map;
+mod options;
+mod oren_nayar;
+mod orthographic;
+mod panorama;
 mod point;
+mod quad;
 mod ray;
+mod region;
+mod render;
+mod rng;
+mod sampler;
+mod sky;
 mod sphere;
+mod stereo;
+mod texture;
+mod thin_film;
+mod tonemap;
+mod transform;
 mod utility;
 mod vector3;
+use animation::{frame_file_name, Animation};
+use aov::{AovKind, AovOutput};
+use aperture::{Aperture, CircularAperture, ImageAperture, PolygonAperture};
+use background::{Background, SkyGradient};
 use color::Color;
 use dielectric::Dielectric;
-use hittable::Hittable;
+use environment::EnvironmentMap;
+use film::Film;
+use filter::create_filter;
+use fisheye::FisheyeCamera;
 use lambertian::Lambertian;
+use lens::{LensSystem, RealisticCamera};
 use metal::Metal;
+use options::{Options, USAGE};
+use orthographic::OrthographicCamera;
+use panorama::{CubemapCamera, EquirectangularCamera};
 use point::Point;
 use ray::Ray;
-use std::fs::File;
-use std::io::{BufWriter, Write};
+use render::Renderer;
+use rng::Pcg32;
+use sampler::create_sampler;
+use sky::PhysicalSky;
 use std::rc::Rc;
-use utility::{rand, INF};
+use stereo::{StereoCamera, StereoLayout};
 use vector3::Vector3;
 
-use crate::camera::Camera;
+use crate::camera::{
+    aperture_diameter, Camera, CameraBasis, CameraKind, CameraPose, PerspectiveCamera,
+};
+use crate::hittable::Hittable;
 use crate::hittable_vec::HittableVec;
 use crate::sphere::Sphere;
 // use crate::utility::PI;
@@ -33,83 +81,226 @@ use crate::sphere::Sphere;
 const ASPECT_RATIO: f64 = 3.0 / 2.0;
 const IMAGE_WIDTH: i32 = 1200;
 const IMAGE_HEIGHT: i32 = (IMAGE_WIDTH as f64 / ASPECT_RATIO) as i32;
-const SAMPLES_PER_PIXEL: i32 = 500;
-const MAX_DEPTH: i32 = 50;
 
 fn main() {
+    let mut options = Options::parse(std::env::args().skip(1)).unwrap_or_else(|message| {
+        if !message.is_empty() {
+            eprintln!("{message}");
+        }
+        eprintln!("{USAGE}");
+        std::process::exit(1);
+    });
+    let (width, height) = image_size(&options);
+    if options
+        .window(width, height)
+        .is_some_and(|window| window.is_empty())
+    {
+        eprintln!("The render window is outside the {width}x{height} image");
+        std::process::exit(1);
+    }
+    if let Some(file_name) = options.animation.clone() {
+        render_animation(&file_name, &mut options);
+        return;
+    }
+
+    // picking up an earlier render also brings back the settings it was started with
+    let mut film = match options.resume.clone() {
+        Some(file_name) => resume_film(&file_name, &mut options),
+        None => {
+            let (width, height) = image_size(&options);
+            Film::new(width, height)
+        }
+    };
+    if options.checkpoint.is_some() {
+        interrupt::catch_interrupt();
+    }
+
     // World
-    let world = random_scene();
+    let world = random_scene(options.seed);
+    let background = create_background(&options);
 
     // camera
-    let camera = create_camera();
+    let camera = create_camera(&options, &world, default_camera_pose());
 
-    // create ppm file
-    let file_name = "output.ppm";
-    let f = File::create(file_name).expect("Unable to create file");
-    let mut f = BufWriter::new(f);
+    let finished = render_frame(
+        &options,
+        &world,
+        background.as_ref(),
+        camera.as_ref(),
+        &mut film,
+    );
+    if !finished {
+        let checkpoint = options.checkpoint.as_deref().unwrap_or_default();
+        println!();
+        println!("Render interrupted, resume it with --resume {checkpoint}");
+        return;
+    }
+    // keep the finished render too, so it can be resumed later to a higher sample count
+    if let Some(file_name) = &options.checkpoint {
+        checkpoint::save(file_name, &options, &film).expect("Unable to save checkpoint");
+    }
+    println!();
+    println!("Done Rendering! 😀");
+}
+// Renders the film and writes the image and any other outputs asked for, returning whether
+// the render got to the end.
+fn render_frame(
+    options: &Options,
+    world: &HittableVec,
+    background: &dyn Background,
+    camera: &dyn Camera,
+    film: &mut Film,
+) -> bool {
+    // the denoiser is guided by the aovs
+    if options.denoise || !options.aovs.is_empty() {
+        film.record_aovs();
+    }
+    let mut sampler = create_sampler(options.sampler, options.strata(), options.seed);
+    let filter = create_filter(options.filter, options.filter_radius());
+
+    let renderer = Renderer::new(world, background, camera, filter.as_ref(), options);
+    let finished = if options.progressive {
+        renderer.render_progressive(film, sampler.as_mut())
+    } else {
+        renderer.render(film, sampler.as_mut())
+    };
 
     // write to ppm file to render an image
-    writeln!(f, "P3").expect("unable to write");
-    writeln!(f, "{IMAGE_WIDTH} {IMAGE_HEIGHT}").expect("unable to write");
-    writeln!(f, "255").expect("unable to write");
-
-    for j in (0..IMAGE_HEIGHT).rev() {
-        print!("\x1B[2J\x1B[1;1H");
-        println!("Scanlines remaining: {j}");
-        for i in 0..IMAGE_WIDTH {
-            let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-            for _ in 0..SAMPLES_PER_PIXEL {
-                let u = (i as f64 + rand()) / (IMAGE_WIDTH - 1) as f64;
-                let v = (j as f64 + rand()) / (IMAGE_HEIGHT - 1) as f64;
-                let r = camera.get_ray(u, v);
-                pixel_color += ray_color(&r, &world, MAX_DEPTH);
-            }
-            pixel_color.write(&mut f, SAMPLES_PER_PIXEL as f64);
+    renderer.write_output(film);
+    if let Some(heatmap) = &options.heatmap {
+        film.write_sample_heatmap(heatmap, options.samples);
+    }
+    if let Some(aovs) = film.aovs() {
+        let crop = options
+            .window(film.width(), film.height())
+            .filter(|_| options.crop);
+        for output in &options.aovs {
+            aovs.write(output, crop.as_ref())
+                .expect("Unable to write aov image");
         }
     }
-    println!();
+    finished
+}
+// Renders each frame of an animation to its own numbered files, with the scene and camera
+// posed as the keyframes say.
+fn render_animation(file_name: &str, options: &mut Options) {
+    let animation = Animation::load(file_name).expect("Unable to load animation");
+    let frames = options.frames.unwrap_or_else(|| animation.frames());
+    let objects = random_scene(options.seed).len();
+    if animation.max_object() > objects {
+        eprintln!(
+            "{file_name} animates object {}, but the scene only has {objects}",
+            animation.max_object()
+        );
+        std::process::exit(1);
+    }
+    let background = create_background(options);
+
+    let output = options.output.clone();
+    let heatmap = options.heatmap.clone();
+    let aovs: Vec<(AovKind, String)> = options
+        .aovs
+        .iter()
+        .map(|aov| (aov.kind, aov.file_name.clone()))
+        .collect();
+    for frame in frames.first..=frames.last {
+        options.output = frame_file_name(&output, frame);
+        options.heatmap = heatmap.as_deref().map(|name| frame_file_name(name, frame));
+        options.aovs = aovs
+            .iter()
+            .map(|(kind, name)| AovOutput {
+                kind: *kind,
+                file_name: frame_file_name(name, frame),
+            })
+            .collect();
+
+        let mut world = random_scene(options.seed);
+        animation.pose_world(frame as f64, &mut world);
+        let pose = animation.camera_pose(frame as f64, default_camera_pose());
+        let camera = create_camera(options, &world, pose);
+        let (width, height) = image_size(options);
+        let mut film = Film::new(width, height);
+        render_frame(
+            options,
+            &world,
+            background.as_ref(),
+            camera.as_ref(),
+            &mut film,
+        );
+        println!();
+        println!("Frame {frame} of {}-{} done", frames.first, frames.last);
+    }
     println!("Done Rendering! 😀");
 }
+fn resume_film(file_name: &str, options: &mut Options) -> Film {
+    let film = checkpoint::load(file_name, options).expect("Unable to load checkpoint");
+    if (film.width(), film.height()) != image_size(options) {
+        eprintln!("{file_name} was saved from a render of a different size");
+        std::process::exit(1);
+    }
+    // keep saving progress to the same checkpoint unless told otherwise
+    if options.checkpoint.is_none() {
+        options.checkpoint = Some(file_name.to_string());
+    }
+    film
+}
 
-fn ray_color<T: Hittable>(r: &Ray, world: &T, depth: i32) -> Color {
-    if depth <= 0 {
-        // If this is true then at this point we have exceeded the ray bounce limit.
-        // Since the light will never not hit the hittable object, we say no light is gathered.
-        return Color::new(0.0, 0.0, 0.0);
-    }
-    if let Some(x) = world.hit(r, 0.001, INF) {
-        let scatter_result = x.material.scatter(r, &x);
-        if scatter_result.success {
-            return scatter_result.attenuation
-                * ray_color(&scatter_result.scattered, world, depth - 1);
-        }
-        return Color::new(0.0, 0.0, 0.0);
+// Panoramas take in twice as much longitude as latitude, so they get an image twice as wide
+// as it is tall.
+// Stereo renders put the two eyes' images next to or on top of each other.
+fn image_size(options: &Options) -> (i32, i32) {
+    let (width, height) = match options.camera {
+        CameraKind::Equirectangular => (IMAGE_WIDTH, IMAGE_WIDTH / 2),
+        _ => (IMAGE_WIDTH, IMAGE_HEIGHT),
+    };
+    match options.stereo {
+        Some(StereoLayout::SideBySide) => (2 * width, height),
+        Some(StereoLayout::TopBottom) => (width, 2 * height),
+        None => (width, height),
+    }
+}
+
+fn create_background(options: &Options) -> Box<dyn Background> {
+    match &options.environment {
+        Some(file_name) => Box::new(
+            EnvironmentMap::load(
+                file_name,
+                options.environment_rotation,
+                options.environment_intensity,
+            )
+            .expect("Unable to load environment map"),
+        ),
+        None if options.sky => Box::new(PhysicalSky::new(
+            options.sun_elevation,
+            options.sun_azimuth,
+            options.turbidity,
+            options.sky_intensity,
+        )),
+        None => Box::new(SkyGradient::new()),
     }
-    let unit_direction = Vector3::unit_vector(r.direction());
-    let t = 0.5 * (unit_direction.y() + 1.0);
-    Color::new(1.0, 1.0, 1.0) * (1.0 - t) + Color::new(0.5, 0.7, 1.0) * t
 }
 
-fn random_scene() -> HittableVec {
+fn random_scene(seed: u64) -> HittableVec {
     let mut world = HittableVec::new();
+    let mut rng = Pcg32::new(seed, 0);
 
     let ground_material = Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
     world.push(Box::new(Sphere::new(Point::new(0.0, -1000.0, 0.0), 1000.0, ground_material)));
 
     for a in -11..11 {
         for b in -11..11 {
-            let choose_mat = utility::rand();
-            let center = Point::new((a as f64) + 0.9*utility::rand(), 0.2, (b as f64) + 0.9*utility::rand());
+            let choose_mat = rng.next_f64();
+            let center = Point::new((a as f64) + 0.9*rng.next_f64(), 0.2, (b as f64) + 0.9*rng.next_f64());
 
             if (center.clone() - Point::new(4.0, 0.2, 0.0)).length() > 0.9 {
                 if choose_mat < 0.60 {
-                    let albedo = Color::from_vector(Vector3::new_random()) * Color::from_vector(Vector3::new_random());
+                    let albedo = Color::from_vector(Vector3::new_random_from(&mut rng, 0.0, 1.0)) * Color::from_vector(Vector3::new_random_from(&mut rng, 0.0, 1.0));
                     let sphere_material = Rc::new(Lambertian::new(albedo));
                     world.push(Box::new(Sphere::new(center, 0.2, sphere_material)));
                 }
                 else if choose_mat < 0.85 {
-                    let albedo = Color::from_vector(Vector3::new_random_range(0.5, 1.0));
-                    let fuzz = utility::rand_range(0.0, 0.5);
+                    let albedo = Color::from_vector(Vector3::new_random_from(&mut rng, 0.5, 1.0));
+                    let fuzz = rng.next_range(0.0, 0.5);
                     let sphere_material = Rc::new(Metal::new(albedo, fuzz));
                     world.push(Box::new(Sphere::new(center, 0.2, sphere_material)));
                 }
@@ -134,20 +325,128 @@ fn random_scene() -> HittableVec {
     world
 }
 
-fn create_camera() -> Camera {
-    let lookfrom = Point::new(13.0, 2.0, 3.0);
-    let lookat = Point::new(0.0, 0.0, 0.0);
+fn default_camera_pose() -> CameraPose {
+    CameraPose::new(Point::new(13.0, 2.0, 3.0), Point::new(0.0, 0.0, 0.0))
+}
+fn create_camera(options: &Options, world: &HittableVec, pose: CameraPose) -> Box<dyn Camera> {
     let vup = Vector3::new(0.0, 1.0, 0.0);
-    let focus_dist = 10.0;
-    let aperture = 0.1;
-
-    Camera::new(
-        lookfrom,
-        lookat,
-        vup,
-        20.0,
-        ASPECT_RATIO,
-        aperture,
-        focus_dist,
-    )
+    let center = CameraBasis::new(pose.lookfrom, pose.lookat, vup);
+    let focus_dist = match pose.focus_distance {
+        Some(distance) => distance,
+        None if options.autofocus => autofocus(&center, world),
+        None => options.focus_distance.unwrap_or(10.0),
+    };
+    let fov = pose.fov.or(options.fov);
+
+    match options.stereo {
+        Some(layout) => Box::new(StereoCamera::new(
+            create_eye_camera(options, &center, -options.ipd / 2.0, focus_dist, fov),
+            create_eye_camera(options, &center, options.ipd / 2.0, focus_dist, fov),
+            layout,
+        )),
+        None => create_eye_camera(options, &center, 0.0, focus_dist, fov),
+    }
+}
+// How far along the view direction the first thing at the center of the image is, or the
+// usual focus distance when there's nothing there.
+fn autofocus(center: &CameraBasis, world: &HittableVec) -> f64 {
+    let direction = -center.w.clone();
+    let ray = Ray::new(center.origin.clone(), direction);
+    match world.hit(&ray, 0.001, f64::INFINITY) {
+        Some(rec) => rec.t,
+        None => {
+            eprintln!("Nothing to focus on at the center of the image, focusing at 10");
+            10.0
+        }
+    }
+}
+fn create_aperture(options: &Options) -> Box<dyn Aperture> {
+    match (&options.aperture_image, options.aperture_blades) {
+        (Some(file_name), _) => {
+            Box::new(ImageAperture::load(file_name).expect("Unable to load aperture image"))
+        }
+        (None, Some(blades)) => Box::new(PolygonAperture::new(blades, options.aperture_rotation)),
+        (None, None) => Box::new(CircularAperture),
+    }
+}
+fn create_realistic_camera(
+    options: &Options,
+    basis: CameraBasis,
+    focus_dist: f64,
+) -> RealisticCamera {
+    let file_name = options.lens.as_deref().unwrap_or_default();
+    let mut lens = LensSystem::load(file_name).expect("Unable to load lens");
+    if let Some(diameter) = options.lens_stop {
+        lens = lens
+            .with_stop_diameter(diameter)
+            .expect("Unable to stop down lens");
+    }
+    RealisticCamera::new(basis, lens, options.sensor.width, ASPECT_RATIO, focus_dist)
+        .expect("Unable to focus lens")
+}
+fn create_eye_camera(
+    options: &Options,
+    center: &CameraBasis,
+    eye_offset: f64,
+    focus_dist: f64,
+    fov: Option<f64>,
+) -> Box<dyn Camera> {
+    let vfov = match options.focal_length {
+        Some(length) => options.sensor.vfov(length, ASPECT_RATIO),
+        None => fov.unwrap_or(20.0),
+    };
+    let aperture = match options.f_stop {
+        Some(f_stop) => {
+            let length = options
+                .focal_length
+                .unwrap_or_else(|| options.sensor.focal_length(vfov, ASPECT_RATIO));
+            aperture_diameter(length, f_stop)
+        }
+        None => 0.1,
+    };
+    let basis = center.offset(eye_offset);
+
+    match options.camera {
+        CameraKind::Perspective if options.lens.is_some() => {
+            Box::new(create_realistic_camera(options, basis, focus_dist))
+        }
+        CameraKind::Perspective => {
+            // Off-axis stereo slides each eye's image back toward the middle so both line up
+            // at the convergence distance, where things appear at the depth of the screen.
+            let shift = if options.parallel {
+                0.0
+            } else {
+                -eye_offset * focus_dist / options.convergence.unwrap_or(focus_dist)
+            };
+            Box::new(
+                PerspectiveCamera::new(basis, vfov, ASPECT_RATIO, aperture, focus_dist)
+                    .with_shift(shift)
+                    .with_lens_shift(options.shift_x, options.shift_y)
+                    .with_tilt(options.tilt, options.swing)
+                    .with_aperture(create_aperture(options))
+                    .with_vignetting(options.vignetting),
+            )
+        }
+        CameraKind::Orthographic => {
+            let (width, height) = match (options.view_width, options.view_height) {
+                (Some(width), Some(height)) => (width, height),
+                (Some(width), None) => (width, width / ASPECT_RATIO),
+                (None, height) => {
+                    let height = height.unwrap_or(4.0);
+                    (height * ASPECT_RATIO, height)
+                }
+            };
+            Box::new(OrthographicCamera::new(basis, width, height))
+        }
+        CameraKind::Fisheye => Box::new(FisheyeCamera::new(
+            basis,
+            fov.unwrap_or(180.0),
+            ASPECT_RATIO,
+            options.fisheye_mapping,
+        )),
+        CameraKind::Equirectangular => {
+            Box::new(EquirectangularCamera::new(center.clone()).with_eye_offset(eye_offset))
+        }
+        CameraKind::Cubemap => Box::new(CubemapCamera::new(basis.origin)),
+    }
 }
@@ -1,4 +1,4 @@
-use crate::{color::Color, hittable::HitRecord, ray::Ray};
+use crate::{color::Color, hittable::HitRecord, ray::Ray, sampler::Sampler, vector3::Vector3};
 
 pub struct ScatterResult {
     pub success: bool,
@@ -17,5 +17,18 @@ impl ScatterResult {
 }
 
 pub trait Material {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> ScatterResult;
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> ScatterResult;
+
+    // For diffuse materials, the brdf times the cosine term for light arriving from
+    // direction, along with the pdf that scatter() picks that direction with. This is what
+    // lets lights be sampled directly. Mirror-like materials leave it as None.
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _direction: &Vector3) -> Option<(Color, f64)> {
+        None
+    }
+
+    // The color the surface reflects overall, without any lighting, for the albedo output
+    // that compositing and denoising use. Clear and mirror-like materials are white.
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
 }
@@ -3,34 +3,71 @@ use crate::{
     hittable::HitRecord,
     material::{Material, ScatterResult},
     ray::Ray,
+    sampler::Sampler,
+    thin_film::ThinFilm,
     vector3::Vector3,
 };
 
 pub struct Metal {
     albedo: Color,
     fuzz: f64,
+    film: Option<ThinFilm>,
 }
 
 #[allow(dead_code)]
 impl Metal {
     pub fn new(albedo: Color, fuzz: f64) -> Metal {
-        Metal { albedo, fuzz }
+        Metal {
+            albedo,
+            fuzz,
+            film: None,
+        }
+    }
+    pub fn with_thin_film(mut self, film: ThinFilm) -> Metal {
+        self.film = Some(film);
+        self
+    }
+
+    // Index of refraction that gives each channel of the albedo as its reflectance at normal
+    // incidence. It stands in for the metal underneath a thin film.
+    fn substrate_ir(&self) -> [f64; 3] {
+        let ir = |reflectance: f64| {
+            let r = reflectance.clamp(0.0, 0.99).sqrt();
+            (1.0 + r) / (1.0 - r)
+        };
+        [
+            ir(self.albedo.red()),
+            ir(self.albedo.green()),
+            ir(self.albedo.blue()),
+        ]
     }
 }
 
 impl Material for Metal {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> ScatterResult {
-        let reflected = Vector3::reflect(&Vector3::unit_vector(r_in.direction()), &rec.normal);
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> ScatterResult {
+        let unit_direction = Vector3::unit_vector(r_in.direction());
+        let reflected = Vector3::reflect(&unit_direction, &rec.normal);
         let scattered = Ray::new(
             rec.point.clone(),
-            reflected + (Vector3::random_in_unit_sphere() * self.fuzz),
+            reflected
+                + (Vector3::sample_in_unit_sphere(sampler.get_2d(), sampler.get_1d()) * self.fuzz),
         );
-        let attenuation = self.albedo.clone();
-        let success = Vector3::dot(scattered.direction(), &rec.normal) >= 0.0;
+        let attenuation = match &self.film {
+            Some(film) => {
+                let cos_theta = Vector3::dot(&(-unit_direction), &rec.normal).min(1.0);
+                film.reflectance(cos_theta, 1.0, self.substrate_ir())
+            }
+            None => self.albedo.clone(),
+        };
+        let success = Vector3::dot(scattered.direction(), &rec.normal) >= 0.0
+            && !rec.below_surface(scattered.direction());
         ScatterResult {
             success,
             attenuation,
             scattered,
         }
     }
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        self.albedo.clone()
+    }
 }
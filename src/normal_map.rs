@@ -0,0 +1,121 @@
+use std::rc::Rc;
+
+use crate::{
+    hittable::{HitRecord, Hittable},
+    ray::Ray,
+    texture::Texture,
+    vector3::Vector3,
+};
+
+// step in texture space used to take the slope of a bump map
+const BUMP_DELTA: f64 = 0.0005;
+
+pub enum Perturbation {
+    // rgb of the texture encodes a tangent space normal, (0.5, 0.5, 1.0) leaves it untouched
+    NormalMap(Rc<dyn Texture>),
+    // brightness of the texture is a height offset along the normal, multiplied by scale
+    BumpMap(Rc<dyn Texture>, f64),
+}
+
+// Wraps any object and bends the shading normal of its hits using a normal or bump map.
+// The geometric normal is kept as is so materials can tell when a ray really left the surface.
+pub struct NormalMapped<H: Hittable> {
+    object: H,
+    perturbation: Perturbation,
+}
+
+#[allow(dead_code)]
+impl<H: Hittable> NormalMapped<H> {
+    pub fn new(object: H, perturbation: Perturbation) -> NormalMapped<H> {
+        NormalMapped {
+            object,
+            perturbation,
+        }
+    }
+    pub fn normal_map(object: H, texture: Rc<dyn Texture>) -> NormalMapped<H> {
+        NormalMapped::new(object, Perturbation::NormalMap(texture))
+    }
+    pub fn bump_map(object: H, height: Rc<dyn Texture>, scale: f64) -> NormalMapped<H> {
+        NormalMapped::new(object, Perturbation::BumpMap(height, scale))
+    }
+
+    fn perturbed_normal(&self, rec: &HitRecord, outward: &Vector3) -> Vector3 {
+        match &self.perturbation {
+            Perturbation::NormalMap(texture) => {
+                let (tangent, bitangent) = tangent_frame(rec, outward);
+                let c = texture.value(rec.u, rec.v, &rec.point);
+                let local = Vector3::new(
+                    2.0 * c.red() - 1.0,
+                    2.0 * c.green() - 1.0,
+                    2.0 * c.blue() - 1.0,
+                );
+                tangent * local.x() + bitangent * local.y() + outward.clone() * local.z()
+            }
+            Perturbation::BumpMap(height, scale) => {
+                let displacement = |du: f64, dv: f64| {
+                    let p = rec.point.clone() + rec.dpdu.clone() * du + rec.dpdv.clone() * dv;
+                    height.value(rec.u + du, rec.v + dv, &p).luminance() * scale
+                };
+                let d = displacement(0.0, 0.0);
+                let du = (displacement(BUMP_DELTA, 0.0) - d) / BUMP_DELTA;
+                let dv = (displacement(0.0, BUMP_DELTA) - d) / BUMP_DELTA;
+
+                // moving the surface along its normal tilts both tangents
+                let dpdu = rec.dpdu.clone() + outward.clone() * du;
+                let dpdv = rec.dpdv.clone() + outward.clone() * dv;
+                let normal = Vector3::cross(&dpdu, &dpdv);
+                if Vector3::dot(&normal, outward) < 0.0 {
+                    -normal
+                } else {
+                    normal
+                }
+            }
+        }
+    }
+}
+
+impl<H: Hittable> Hittable for NormalMapped<H> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut rec = self.object.hit(r, t_min, t_max)?;
+
+        // maps are defined against the outside of the surface
+        let outward = if rec.front_face {
+            rec.geometric_normal.clone()
+        } else {
+            -rec.geometric_normal.clone()
+        };
+        let perturbed = self.perturbed_normal(&rec, &outward);
+        if perturbed.near_zero() {
+            return Some(rec);
+        }
+        let perturbed = Vector3::unit_vector(&perturbed);
+        let normal = if rec.front_face {
+            perturbed
+        } else {
+            -perturbed
+        };
+
+        // a normal facing away from the viewer can't be shaded sensibly, so keep the real one
+        if Vector3::dot(&normal, r.direction()) < 0.0 {
+            rec.normal = normal;
+        }
+        Some(rec)
+    }
+}
+
+// Orthonormal tangent and bitangent around the normal, with the tangent following dp/du.
+fn tangent_frame(rec: &HitRecord, normal: &Vector3) -> (Vector3, Vector3) {
+    let mut tangent = rec.dpdu.clone() - normal.clone() * Vector3::dot(normal, &rec.dpdu);
+    if tangent.near_zero() {
+        // dp/du vanishes at the poles of a sphere, so any direction in the tangent plane will do
+        let axis = if normal.x().abs() > 0.9 {
+            Vector3::new(0.0, 1.0, 0.0)
+        } else {
+            Vector3::new(1.0, 0.0, 0.0)
+        };
+        tangent = Vector3::cross(&axis, normal);
+    }
+    let tangent = Vector3::unit_vector(&tangent);
+    let bitangent = Vector3::cross(normal, &tangent);
+    (tangent, bitangent)
+}
@@ -0,0 +1,682 @@
+use std::str::FromStr;
+
+use crate::{
+    animation::FrameRange,
+    aov::AovOutput,
+    camera::{exposure_stops, CameraKind, Sensor},
+    filter::FilterKind,
+    fisheye::FisheyeMapping,
+    region::{Region, Window},
+    sampler::SamplerKind,
+    stereo::StereoLayout,
+    tonemap::{ToneMapper, ToneMapping},
+};
+
+pub const USAGE: &str = "\
+usage: ray-tracer [output.ppm] [options]
+
+options:
+  --samples <count>          samples per pixel, or the most a pixel may take when adaptive
+                             (default 500)
+  --noise-threshold <error>  sample adaptively, stopping each pixel once its estimated
+                             relative error is below this, e.g. 0.01
+  --min-samples <count>      samples every pixel takes before it may stop (default 16)
+  --heatmap <file.ppm>       also write an image of how many samples each pixel took
+  --aov <name>=<file>        also write what the camera rays hit first, one of albedo,
+                             normal, depth, position, object-id or material-id, to a .pfm
+                             file with the raw values or a .ppm file to look at. Can be
+                             given more than once
+  --denoise                  smooth away the noise left in the image, guided by the albedo,
+                             normal and depth of what the camera sees
+  --denoise-strength <k>     how different neighbors may look and still be blended by
+                             --denoise (default 0.45)
+  --progressive              render in passes over the whole image, saving it after each
+  --snapshot-passes <count>  with --progressive, save the image every this many passes
+                             (default 1)
+  --snapshot-seconds <secs>  with --progressive, also save whenever this much time has passed
+  --time-limit <secs>        with --progressive, stop after the pass that runs past this
+  --window <x>,<y>,<w>,<h>   only trace the pixels of this rectangle, counted from the top
+                             left, leaving the rest of the image black
+  --region <x0>,<y0>,<x1>,<y1>  the same as --window, as fractions of the image size, e.g.
+                             0.25,0.25,0.75,0.75 for the middle
+  --crop                     with --window or --region, write only the rectangle rendered
+  --composite <file.ppm>     with --window or --region, paste the rectangle rendered over
+                             an earlier full size render instead of black
+  --env <file>               light the scene with an equirectangular .hdr, .pfm or .ppm image
+  --env-rotation <degrees>   rotate the environment map about the vertical axis
+  --env-intensity <scale>    multiply the brightness of the environment map
+  --sky                      light the scene with a physical daylight sky and sun
+  --sun-elevation <degrees>  height of the sun above the horizon (default 45)
+  --sun-azimuth <degrees>    direction of the sun, 0 is along +x and 90 along +z
+  --turbidity <value>        haziness of the sky from 2 (clear) to 10 (murky), default 3
+  --sky-intensity <scale>    multiply the brightness of the sky and sun
+  --sampler <name>           independent (default), stratified, halton or sobol
+  --strata <count>           samples the stratified sampler lays its strata out for
+                             (default --samples)
+  --camera <name>            perspective (default), orthographic, fisheye, equirectangular
+                             (a 360 degree panorama) or cubemap (six faces in a 3x2 grid)
+  --fov <degrees>            field of view, up and down for perspective (default 20) and
+                             across the circle for fisheye (default 180)
+  --focal-length <mm>        focal length of the perspective camera's lens, sets the field
+                             of view together with --sensor
+  --sensor <size>            full-frame (default), aps-c, micro-four-thirds or the width and
+                             height in millimeters, e.g. 36x24
+  --f-stop <number>          f-number of the lens, sets the size of the aperture taking scene
+                             units as meters
+  --focus-distance <units>   distance to the plane in sharp focus (default 10)
+  --autofocus                focus on whatever is at the center of the image
+  --lens <file>              trace the perspective camera's rays through every element of a
+                             real lens, from a prescription of radius, thickness, index of
+                             refraction and diameter per surface in millimeters
+  --lens-stop <mm>           stop the --lens down to an opening of this diameter
+  --shift-x <fraction>       slide the perspective camera's image sideways by this fraction
+                             of its width, like a shift lens
+  --shift-y <fraction>       slide the image up by this fraction of its height, to take in
+                             tall buildings without tipping the camera
+  --tilt <degrees>           lean the plane of focus back, top away from the camera
+  --swing <degrees>          turn the plane of focus, right side away from the camera
+  --shutter <seconds>        exposure time, e.g. 1/125, brightens the image with --iso and
+                             --f-stop (default 1/100)
+  --iso <number>             sensor sensitivity (default 100)
+  --fisheye-mapping <name>   equidistant (default) or equisolid
+  --view-width <units>       width of the world an orthographic camera takes in
+  --view-height <units>      height of the world an orthographic camera takes in, either
+                             one follows from the other and the image shape (default 4)
+  --stereo <layout>          render both eyes for VR, side-by-side or top-bottom
+  --ipd <units>              distance between the eyes (default 0.065)
+  --convergence <distance>   how far away things line up in both eyes (default the focus
+                             distance)
+  --parallel                 keep the eyes' views parallel instead of lining them up
+  --aperture-blades <count>  give out of focus highlights the shape of a polygon with this
+                             many sides instead of a circle
+  --aperture-rotation <degrees>  turn the polygon of --aperture-blades
+  --aperture-image <file>    give out of focus highlights the shape of the bright parts of
+                             an image, e.g. a star or a heart
+  --vignetting <strength>    let the lens barrel cut into the aperture away from the center,
+                             squeezing highlights into cat's eyes and darkening the corners,
+                             1 is strong (default 0)
+  --filter <name>            pixel reconstruction filter: box (default), tent, gaussian,
+                             mitchell or lanczos
+  --filter-radius <pixels>   how far each sample reaches (default depends on the filter)
+  --exposure <stops>         brighten (or darken when negative) the image written
+  --tonemap <name>           how bright colors are fit into the image: clamp (default),
+                             reinhard, extended-reinhard, hable or aces
+  --white <luminance>        with extended-reinhard, the brightness that becomes white
+                             (default the brightest pixel)
+  --srgb                     encode the image with the sRGB curve instead of gamma 2
+  --animation <file>         render the frames of a keyframed camera and object animation,
+                             numbering the output files like output_0001.ppm
+  --frames <first>-<last>    with --animation, the frames to render (default from the first
+                             keyframe to the last)
+  --seed <number>            seed for the random scene and samples, the same seed always
+                             gives the same image (default 0)
+  --checkpoint <file>        save the render's progress to this file now and then and on
+                             Ctrl-C, so it can be resumed
+  --checkpoint-seconds <secs>  how often to save the checkpoint (default 60)
+  --resume <file>            continue the render saved in a checkpoint, up to --samples
+  --help                     show this message";
+
+// Settings picked on the command line. Anything not given keeps the default render.
+pub struct Options {
+    pub output: String,
+    pub samples: u32,
+    pub noise_threshold: Option<f64>,
+    pub min_samples: u32,
+    pub heatmap: Option<String>,
+    pub aovs: Vec<AovOutput>,
+    pub denoise: bool,
+    pub denoise_strength: f64,
+    pub progressive: bool,
+    pub snapshot_passes: u32,
+    pub snapshot_seconds: Option<f64>,
+    pub time_limit: Option<f64>,
+    pub window: Option<Window>,
+    pub region: Option<Region>,
+    pub crop: bool,
+    pub composite: Option<String>,
+    pub environment: Option<String>,
+    pub environment_rotation: f64,
+    pub environment_intensity: f64,
+    pub sky: bool,
+    pub sun_elevation: f64,
+    pub sun_azimuth: f64,
+    pub turbidity: f64,
+    pub sky_intensity: f64,
+    pub sampler: SamplerKind,
+    pub strata: Option<u32>,
+    pub camera: CameraKind,
+    pub fov: Option<f64>,
+    pub focal_length: Option<f64>,
+    pub sensor: Sensor,
+    pub f_stop: Option<f64>,
+    pub focus_distance: Option<f64>,
+    pub autofocus: bool,
+    pub lens: Option<String>,
+    pub lens_stop: Option<f64>,
+    pub shift_x: f64,
+    pub shift_y: f64,
+    pub tilt: f64,
+    pub swing: f64,
+    pub shutter: Option<f64>,
+    pub iso: Option<f64>,
+    pub fisheye_mapping: FisheyeMapping,
+    pub view_width: Option<f64>,
+    pub view_height: Option<f64>,
+    pub stereo: Option<StereoLayout>,
+    pub ipd: f64,
+    pub convergence: Option<f64>,
+    pub parallel: bool,
+    pub aperture_blades: Option<u32>,
+    pub aperture_rotation: f64,
+    pub aperture_image: Option<String>,
+    pub vignetting: f64,
+    pub filter: FilterKind,
+    pub filter_radius: Option<f64>,
+    pub exposure: f64,
+    pub tonemap: ToneMapper,
+    pub white: Option<f64>,
+    pub srgb: bool,
+    pub animation: Option<String>,
+    pub frames: Option<FrameRange>,
+    pub seed: u64,
+    pub checkpoint: Option<String>,
+    pub checkpoint_seconds: f64,
+    pub resume: Option<String>,
+}
+
+impl Options {
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+        let mut options = Options {
+            output: String::from("output.ppm"),
+            samples: 500,
+            noise_threshold: None,
+            min_samples: 16,
+            heatmap: None,
+            aovs: Vec::new(),
+            denoise: false,
+            denoise_strength: 0.45,
+            progressive: false,
+            snapshot_passes: 1,
+            snapshot_seconds: None,
+            time_limit: None,
+            window: None,
+            region: None,
+            crop: false,
+            composite: None,
+            environment: None,
+            environment_rotation: 0.0,
+            environment_intensity: 1.0,
+            sky: false,
+            sun_elevation: 45.0,
+            sun_azimuth: 0.0,
+            turbidity: 3.0,
+            sky_intensity: 1.0,
+            sampler: SamplerKind::Independent,
+            strata: None,
+            camera: CameraKind::Perspective,
+            fov: None,
+            focal_length: None,
+            sensor: Sensor::new(36.0, 24.0),
+            f_stop: None,
+            focus_distance: None,
+            autofocus: false,
+            lens: None,
+            lens_stop: None,
+            shift_x: 0.0,
+            shift_y: 0.0,
+            tilt: 0.0,
+            swing: 0.0,
+            shutter: None,
+            iso: None,
+            fisheye_mapping: FisheyeMapping::Equidistant,
+            view_width: None,
+            view_height: None,
+            stereo: None,
+            ipd: 0.065,
+            convergence: None,
+            parallel: false,
+            aperture_blades: None,
+            aperture_rotation: 0.0,
+            aperture_image: None,
+            vignetting: 0.0,
+            filter: FilterKind::Box,
+            filter_radius: None,
+            exposure: 0.0,
+            tonemap: ToneMapper::Clamp,
+            white: None,
+            srgb: false,
+            animation: None,
+            frames: None,
+            seed: 0,
+            checkpoint: None,
+            checkpoint_seconds: 60.0,
+            resume: None,
+        };
+        let mut output_set = false;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--samples" => options.samples = value(&arg, &mut args)?,
+                "--noise-threshold" => options.noise_threshold = Some(value(&arg, &mut args)?),
+                "--min-samples" => options.min_samples = value(&arg, &mut args)?,
+                "--heatmap" => options.heatmap = Some(value(&arg, &mut args)?),
+                "--aov" => options.aovs.push(value(&arg, &mut args)?),
+                "--denoise" => options.denoise = true,
+                "--denoise-strength" => options.denoise_strength = value(&arg, &mut args)?,
+                "--progressive" => options.progressive = true,
+                "--snapshot-passes" => options.snapshot_passes = value(&arg, &mut args)?,
+                "--snapshot-seconds" => options.snapshot_seconds = Some(value(&arg, &mut args)?),
+                "--window" => options.window = Some(value(&arg, &mut args)?),
+                "--region" => options.region = Some(value(&arg, &mut args)?),
+                "--crop" => options.crop = true,
+                "--composite" => options.composite = Some(value(&arg, &mut args)?),
+                "--time-limit" => options.time_limit = Some(value(&arg, &mut args)?),
+                "--env" => options.environment = Some(value(&arg, &mut args)?),
+                "--env-rotation" => options.environment_rotation = value(&arg, &mut args)?,
+                "--env-intensity" => options.environment_intensity = value(&arg, &mut args)?,
+                "--sky" => options.sky = true,
+                "--sun-elevation" => options.sun_elevation = value(&arg, &mut args)?,
+                "--sun-azimuth" => options.sun_azimuth = value(&arg, &mut args)?,
+                "--turbidity" => options.turbidity = value(&arg, &mut args)?,
+                "--sky-intensity" => options.sky_intensity = value(&arg, &mut args)?,
+                "--sampler" => options.sampler = value(&arg, &mut args)?,
+                "--strata" => options.strata = Some(value(&arg, &mut args)?),
+                "--camera" => options.camera = value(&arg, &mut args)?,
+                "--fov" => options.fov = Some(value(&arg, &mut args)?),
+                "--focal-length" => options.focal_length = Some(value(&arg, &mut args)?),
+                "--sensor" => options.sensor = value(&arg, &mut args)?,
+                "--f-stop" => options.f_stop = Some(value(&arg, &mut args)?),
+                "--focus-distance" => options.focus_distance = Some(value(&arg, &mut args)?),
+                "--autofocus" => options.autofocus = true,
+                "--lens" => options.lens = Some(value(&arg, &mut args)?),
+                "--lens-stop" => options.lens_stop = Some(value(&arg, &mut args)?),
+                "--shift-x" => options.shift_x = value(&arg, &mut args)?,
+                "--shift-y" => options.shift_y = value(&arg, &mut args)?,
+                "--tilt" => options.tilt = value(&arg, &mut args)?,
+                "--swing" => options.swing = value(&arg, &mut args)?,
+                "--shutter" => options.shutter = Some(seconds(&arg, &mut args)?),
+                "--iso" => options.iso = Some(value(&arg, &mut args)?),
+                "--fisheye-mapping" => options.fisheye_mapping = value(&arg, &mut args)?,
+                "--view-width" => options.view_width = Some(value(&arg, &mut args)?),
+                "--view-height" => options.view_height = Some(value(&arg, &mut args)?),
+                "--stereo" => options.stereo = Some(value(&arg, &mut args)?),
+                "--ipd" => options.ipd = value(&arg, &mut args)?,
+                "--convergence" => options.convergence = Some(value(&arg, &mut args)?),
+                "--parallel" => options.parallel = true,
+                "--aperture-blades" => options.aperture_blades = Some(value(&arg, &mut args)?),
+                "--aperture-rotation" => options.aperture_rotation = value(&arg, &mut args)?,
+                "--aperture-image" => options.aperture_image = Some(value(&arg, &mut args)?),
+                "--vignetting" => options.vignetting = value(&arg, &mut args)?,
+                "--filter" => options.filter = value(&arg, &mut args)?,
+                "--filter-radius" => options.filter_radius = Some(value(&arg, &mut args)?),
+                "--exposure" => options.exposure = value(&arg, &mut args)?,
+                "--tonemap" => options.tonemap = value(&arg, &mut args)?,
+                "--white" => options.white = Some(value(&arg, &mut args)?),
+                "--srgb" => options.srgb = true,
+                "--animation" => options.animation = Some(value(&arg, &mut args)?),
+                "--frames" => options.frames = Some(value(&arg, &mut args)?),
+                "--seed" => options.seed = value(&arg, &mut args)?,
+                "--checkpoint" => options.checkpoint = Some(value(&arg, &mut args)?),
+                "--checkpoint-seconds" => options.checkpoint_seconds = value(&arg, &mut args)?,
+                "--resume" => options.resume = Some(value(&arg, &mut args)?),
+                "--help" | "-h" => return Err(String::new()),
+                _ if arg.starts_with("--") => return Err(format!("unknown option {arg}")),
+                _ if !output_set => {
+                    options.output = arg;
+                    output_set = true;
+                }
+                _ => return Err(format!("unexpected argument {arg}")),
+            }
+        }
+        if options.samples == 0 {
+            return Err(String::from("--samples must be at least 1"));
+        }
+        if options.fov.is_some_and(|fov| fov <= 0.0 || fov > 360.0) {
+            return Err(String::from("--fov must be between 0 and 360"));
+        }
+        if options.fov.is_some() && options.focal_length.is_some() {
+            return Err(String::from(
+                "--fov and --focal-length can't be used together",
+            ));
+        }
+        if options.focal_length.is_some_and(|length| length <= 0.0)
+            || options.f_stop.is_some_and(|f_stop| f_stop <= 0.0)
+            || options
+                .focus_distance
+                .is_some_and(|distance| distance <= 0.0)
+            || options.shutter.is_some_and(|shutter| shutter <= 0.0)
+            || options.iso.is_some_and(|iso| iso <= 0.0)
+        {
+            return Err(String::from(
+                "--focal-length, --f-stop, --focus-distance, --shutter and --iso must be \
+                 greater than 0",
+            ));
+        }
+        if options.focus_distance.is_some() && options.autofocus {
+            return Err(String::from(
+                "--focus-distance and --autofocus can't be used together",
+            ));
+        }
+        if options.lens.is_some()
+            && (options.fov.is_some() || options.focal_length.is_some() || options.f_stop.is_some())
+        {
+            return Err(String::from(
+                "--lens sets the field of view and aperture itself, leave out --fov, \
+                 --focal-length and --f-stop",
+            ));
+        }
+        if options.lens.is_some() && options.camera != CameraKind::Perspective {
+            return Err(String::from(
+                "--lens only works with the perspective camera",
+            ));
+        }
+        if options.lens_stop.is_some() && options.lens.is_none() {
+            return Err(String::from("--lens-stop needs a --lens"));
+        }
+        if options.tilt.abs() >= 90.0 || options.swing.abs() >= 90.0 {
+            return Err(String::from(
+                "--tilt and --swing must be between -90 and 90",
+            ));
+        }
+        if options.view_width.is_some_and(|width| width <= 0.0)
+            || options.view_height.is_some_and(|height| height <= 0.0)
+        {
+            return Err(String::from(
+                "--view-width and --view-height must be greater than 0",
+            ));
+        }
+        if options.stereo.is_some() && options.camera == CameraKind::Cubemap {
+            return Err(String::from(
+                "--stereo doesn't work with the cubemap camera",
+            ));
+        }
+        if options.convergence.is_some_and(|distance| distance <= 0.0) {
+            return Err(String::from("--convergence must be greater than 0"));
+        }
+        if options.aperture_blades.is_some_and(|blades| blades < 3) {
+            return Err(String::from("--aperture-blades must be at least 3"));
+        }
+        if options.aperture_blades.is_some() && options.aperture_image.is_some() {
+            return Err(String::from(
+                "--aperture-blades and --aperture-image can't be used together",
+            ));
+        }
+        if options.vignetting < 0.0 {
+            return Err(String::from("--vignetting can't be negative"));
+        }
+        if options.filter_radius.is_some_and(|radius| radius <= 0.0) {
+            return Err(String::from("--filter-radius must be greater than 0"));
+        }
+        if options.window.is_some() && options.region.is_some() {
+            return Err(String::from("--window and --region can't be used together"));
+        }
+        let has_window = options.window.is_some() || options.region.is_some();
+        if (options.crop || options.composite.is_some()) && !has_window {
+            return Err(String::from(
+                "--crop and --composite need a --window or --region",
+            ));
+        }
+        if options.crop && options.composite.is_some() {
+            return Err(String::from(
+                "--crop and --composite can't be used together",
+            ));
+        }
+        // a .ppm holds colors the way they are written, ready to have new pixels pasted in
+        if options
+            .composite
+            .as_ref()
+            .is_some_and(|file_name| !file_name.to_lowercase().ends_with(".ppm"))
+        {
+            return Err(String::from("--composite needs a .ppm image"));
+        }
+        if options.frames.is_some() && options.animation.is_none() {
+            return Err(String::from("--frames needs an --animation"));
+        }
+        if options.animation.is_some() && (options.checkpoint.is_some() || options.resume.is_some())
+        {
+            return Err(String::from(
+                "--checkpoint and --resume don't work with --animation, render a range of \
+                 --frames at a time instead",
+            ));
+        }
+        if options.sky && options.environment.is_some() {
+            return Err(String::from("--env and --sky can't be used together"));
+        }
+        Ok(options)
+    }
+
+    pub fn strata(&self) -> u32 {
+        self.strata.unwrap_or(self.samples)
+    }
+
+    // The pixels to trace in an image of this size, or None for all of them.
+    pub fn window(&self, width: i32, height: i32) -> Option<Window> {
+        self.window
+            .or_else(|| self.region.map(|region| region.window(width, height)))
+            .map(|window| window.clamp(width, height))
+    }
+
+    pub fn filter_radius(&self) -> f64 {
+        self.filter_radius
+            .unwrap_or_else(|| self.filter.default_radius())
+    }
+
+    // How many stops the shutter speed, ISO and f-number brighten the image by, when a
+    // shutter speed or ISO is given.
+    pub fn camera_exposure(&self) -> f64 {
+        if self.shutter.is_none() && self.iso.is_none() {
+            return 0.0;
+        }
+        exposure_stops(
+            self.f_stop.unwrap_or(16.0),
+            self.shutter.unwrap_or(0.01),
+            self.iso.unwrap_or(100.0),
+        )
+    }
+
+    // How the image files are developed from the film. This isn't part of the render
+    // settings, so a checkpoint can be written out again with a different look.
+    pub fn tone_mapping(&self) -> ToneMapping {
+        let tone_mapping = ToneMapping::new(self.exposure + self.camera_exposure(), self.tonemap)
+            .with_srgb(self.srgb);
+        match self.white {
+            Some(white) => tone_mapping.with_white(white),
+            None => tone_mapping,
+        }
+    }
+
+    // The settings that change what the image looks like, as command line arguments.
+    // Checkpoints store these so a resumed render carries on exactly as it started.
+    pub fn render_settings(&self) -> Vec<String> {
+        let mut args = vec![
+            String::from("--seed"),
+            self.seed.to_string(),
+            String::from("--sampler"),
+            self.sampler.to_string(),
+            String::from("--min-samples"),
+            self.min_samples.to_string(),
+            String::from("--camera"),
+            self.camera.to_string(),
+            String::from("--fisheye-mapping"),
+            self.fisheye_mapping.to_string(),
+            String::from("--filter"),
+            self.filter.to_string(),
+            String::from("--filter-radius"),
+            self.filter_radius().to_string(),
+        ];
+        if self.sampler == SamplerKind::Stratified {
+            // unlike the other samplers, where the samples land depends on how many there are
+            args.extend([String::from("--strata"), self.strata().to_string()]);
+        }
+        if let Some(fov) = self.fov {
+            args.extend([String::from("--fov"), fov.to_string()]);
+        }
+        if let Some(length) = self.focal_length {
+            args.extend([String::from("--focal-length"), length.to_string()]);
+        }
+        if self.focal_length.is_some() || self.f_stop.is_some() || self.lens.is_some() {
+            args.extend([String::from("--sensor"), self.sensor.to_string()]);
+        }
+        if let Some(f_stop) = self.f_stop {
+            args.extend([String::from("--f-stop"), f_stop.to_string()]);
+        }
+        if let Some(distance) = self.focus_distance {
+            args.extend([String::from("--focus-distance"), distance.to_string()]);
+        }
+        if self.autofocus {
+            args.push(String::from("--autofocus"));
+        }
+        if let Some(file_name) = &self.lens {
+            args.extend([String::from("--lens"), file_name.clone()]);
+        }
+        if let Some(diameter) = self.lens_stop {
+            args.extend([String::from("--lens-stop"), diameter.to_string()]);
+        }
+        if self.shift_x != 0.0 || self.shift_y != 0.0 {
+            args.extend([
+                String::from("--shift-x"),
+                self.shift_x.to_string(),
+                String::from("--shift-y"),
+                self.shift_y.to_string(),
+            ]);
+        }
+        if self.tilt != 0.0 || self.swing != 0.0 {
+            args.extend([
+                String::from("--tilt"),
+                self.tilt.to_string(),
+                String::from("--swing"),
+                self.swing.to_string(),
+            ]);
+        }
+        if let Some(width) = self.view_width {
+            args.extend([String::from("--view-width"), width.to_string()]);
+        }
+        if let Some(height) = self.view_height {
+            args.extend([String::from("--view-height"), height.to_string()]);
+        }
+        if let Some(layout) = self.stereo {
+            args.extend([
+                String::from("--stereo"),
+                layout.to_string(),
+                String::from("--ipd"),
+                self.ipd.to_string(),
+            ]);
+            if let Some(distance) = self.convergence {
+                args.extend([String::from("--convergence"), distance.to_string()]);
+            }
+            if self.parallel {
+                args.push(String::from("--parallel"));
+            }
+        }
+        if let Some(blades) = self.aperture_blades {
+            args.extend([
+                String::from("--aperture-blades"),
+                blades.to_string(),
+                String::from("--aperture-rotation"),
+                self.aperture_rotation.to_string(),
+            ]);
+        }
+        if let Some(file_name) = &self.aperture_image {
+            args.extend([String::from("--aperture-image"), file_name.clone()]);
+        }
+        if self.vignetting > 0.0 {
+            args.extend([String::from("--vignetting"), self.vignetting.to_string()]);
+        }
+        if let Some(window) = self.window {
+            args.extend([String::from("--window"), window.to_string()]);
+        }
+        if let Some(region) = self.region {
+            args.extend([String::from("--region"), region.to_string()]);
+        }
+        if let Some(threshold) = self.noise_threshold {
+            args.extend([String::from("--noise-threshold"), threshold.to_string()]);
+        }
+        if let Some(environment) = &self.environment {
+            args.extend([
+                String::from("--env"),
+                environment.clone(),
+                String::from("--env-rotation"),
+                self.environment_rotation.to_string(),
+                String::from("--env-intensity"),
+                self.environment_intensity.to_string(),
+            ]);
+        }
+        if self.sky {
+            args.extend([
+                String::from("--sky"),
+                String::from("--sun-elevation"),
+                self.sun_elevation.to_string(),
+                String::from("--sun-azimuth"),
+                self.sun_azimuth.to_string(),
+                String::from("--turbidity"),
+                self.turbidity.to_string(),
+                String::from("--sky-intensity"),
+                self.sky_intensity.to_string(),
+            ]);
+        }
+        args
+    }
+    pub fn apply_render_settings(&mut self, saved: &Options) {
+        self.seed = saved.seed;
+        self.sampler = saved.sampler;
+        self.strata = saved.strata;
+        self.camera = saved.camera;
+        self.fov = saved.fov;
+        self.fisheye_mapping = saved.fisheye_mapping;
+        self.focal_length = saved.focal_length;
+        self.sensor = saved.sensor;
+        self.f_stop = saved.f_stop;
+        self.focus_distance = saved.focus_distance;
+        self.autofocus = saved.autofocus;
+        self.lens = saved.lens.clone();
+        self.lens_stop = saved.lens_stop;
+        self.shift_x = saved.shift_x;
+        self.shift_y = saved.shift_y;
+        self.tilt = saved.tilt;
+        self.swing = saved.swing;
+        self.view_width = saved.view_width;
+        self.view_height = saved.view_height;
+        self.stereo = saved.stereo;
+        self.ipd = saved.ipd;
+        self.convergence = saved.convergence;
+        self.parallel = saved.parallel;
+        self.aperture_blades = saved.aperture_blades;
+        self.aperture_rotation = saved.aperture_rotation;
+        self.aperture_image = saved.aperture_image.clone();
+        self.vignetting = saved.vignetting;
+        self.filter = saved.filter;
+        self.filter_radius = saved.filter_radius;
+        self.min_samples = saved.min_samples;
+        self.noise_threshold = saved.noise_threshold;
+        self.window = saved.window;
+        self.region = saved.region;
+        self.environment = saved.environment.clone();
+        self.environment_rotation = saved.environment_rotation;
+        self.environment_intensity = saved.environment_intensity;
+        self.sky = saved.sky;
+        self.sun_elevation = saved.sun_elevation;
+        self.sun_azimuth = saved.sun_azimuth;
+        self.turbidity = saved.turbidity;
+        self.sky_intensity = saved.sky_intensity;
+    }
+}
+
+// a time in seconds, either as a number or the way shutter speeds are written, like 1/125
+fn seconds<I: Iterator<Item = String>>(flag: &str, args: &mut I) -> Result<f64, String> {
+    let text: String = value(flag, args)?;
+    let seconds = match text.split_once('/') {
+        Some((numerator, denominator)) => numerator
+            .parse::<f64>()
+            .ok()
+            .zip(denominator.parse::<f64>().ok())
+            .map(|(numerator, denominator)| numerator / denominator),
+        None => text.parse().ok(),
+    };
+    seconds.ok_or_else(|| format!("invalid value {text} for {flag}"))
+}
+
+// Reads the value that follows a flag.
+fn value<T: FromStr, I: Iterator<Item = String>>(flag: &str, args: &mut I) -> Result<T, String> {
+    let text = args.next().ok_or_else(|| format!("{flag} needs a value"))?;
+    text.parse()
+        .map_err(|_| format!("invalid value {text} for {flag}"))
+}
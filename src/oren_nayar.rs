@@ -0,0 +1,96 @@
+use crate::{
+    color::Color,
+    hittable::HitRecord,
+    material::{Material, ScatterResult},
+    ray::Ray,
+    sampler::Sampler,
+    utility::{self, PI},
+    vector3::Vector3,
+};
+
+// Rough diffuse reflector for surfaces like clay, concrete and cloth. A sigma of 0 degrades
+// to a plain lambertian surface, higher values flatten the shading and add retro-reflection.
+pub struct OrenNayar {
+    albedo: Color,
+    a: f64,
+    b: f64,
+}
+
+#[allow(dead_code)]
+impl OrenNayar {
+    pub fn new(albedo: Color, sigma: f64) -> OrenNayar {
+        // sigma is the standard deviation of the microfacet slope angle in degrees
+        let sigma = utility::degrees_to_radians(sigma);
+        let sigma2 = sigma * sigma;
+        let a = 1.0 - sigma2 / (2.0 * (sigma2 + 0.33));
+        let b = 0.45 * sigma2 / (sigma2 + 0.09);
+        OrenNayar { albedo, a, b }
+    }
+
+    // The bracketed Oren-Nayar term for light arriving along wi and leaving along wo,
+    // both pointing away from the surface. The full brdf is albedo / PI times this.
+    fn reflectance(&self, wo: &Vector3, wi: &Vector3, n: &Vector3) -> f64 {
+        let cos_i = Vector3::dot(wi, n);
+        let cos_o = Vector3::dot(wo, n);
+        if cos_i <= 0.0 || cos_o <= 0.0 {
+            return 0.0;
+        }
+        let sin_i = (1.0 - cos_i * cos_i).max(0.0).sqrt();
+        let sin_o = (1.0 - cos_o * cos_o).max(0.0).sqrt();
+
+        // cosine of the azimuthal angle between the two directions in the tangent plane
+        let mut cos_phi = 0.0;
+        if sin_i > 1e-4 && sin_o > 1e-4 {
+            let tangent_i = wi.clone() - n.clone() * cos_i;
+            let tangent_o = wo.clone() - n.clone() * cos_o;
+            cos_phi = (Vector3::dot(&tangent_i, &tangent_o) / (sin_i * sin_o)).max(0.0);
+        }
+
+        // alpha is the larger of the two polar angles and beta the smaller one
+        let (sin_alpha, tan_beta) = if cos_i > cos_o {
+            (sin_o, sin_i / cos_i)
+        } else {
+            (sin_i, sin_o / cos_o)
+        };
+        self.a + self.b * cos_phi * sin_alpha * tan_beta
+    }
+
+    pub fn brdf(&self, wo: &Vector3, wi: &Vector3, n: &Vector3) -> Color {
+        self.albedo.clone() * (self.reflectance(wo, wi, n) / PI)
+    }
+}
+
+impl Material for OrenNayar {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> ScatterResult {
+        // same cosine weighted direction as the lambertian, so brdf * cos / pdf leaves
+        // just the albedo scaled by the Oren-Nayar term
+        let mut scatter_direction =
+            rec.normal.clone() + Vector3::sample_unit_vector(sampler.get_2d());
+        if scatter_direction.near_zero() {
+            scatter_direction = rec.normal.clone()
+        }
+        let wo = -Vector3::unit_vector(r_in.direction());
+        let wi = Vector3::unit_vector(&scatter_direction);
+        let attenuation = self.albedo.clone() * self.reflectance(&wo, &wi, &rec.normal);
+        let success = !rec.below_surface(&scatter_direction);
+        let scattered = Ray::new(rec.point.clone(), scatter_direction);
+        ScatterResult {
+            success,
+            attenuation,
+            scattered,
+        }
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: &Vector3) -> Option<(Color, f64)> {
+        let wi = Vector3::unit_vector(direction);
+        let cosine = Vector3::dot(&wi, &rec.normal);
+        if cosine <= 0.0 || rec.below_surface(direction) {
+            return Some((Color::new(0.0, 0.0, 0.0), 0.0));
+        }
+        let wo = -Vector3::unit_vector(r_in.direction());
+        Some((self.brdf(&wo, &wi, &rec.normal) * cosine, cosine / PI))
+    }
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        self.albedo.clone()
+    }
+}
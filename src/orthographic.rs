@@ -0,0 +1,43 @@
+use crate::{
+    camera::{Camera, CameraBasis},
+    point::Point,
+    ray::Ray,
+    sampler::Sampler,
+    vector3::Vector3,
+};
+
+// A camera whose rays all travel the same way, so things keep their size however far away
+// they are, as in technical drawings and isometric views. The image covers view_width by
+// view_height world units centered on lookfrom.
+pub struct OrthographicCamera {
+    lower_left_corner: Point,
+    horizontal: Vector3,
+    vertical: Vector3,
+    direction: Vector3,
+}
+
+#[allow(dead_code)]
+impl OrthographicCamera {
+    pub fn new(basis: CameraBasis, view_width: f64, view_height: f64) -> OrthographicCamera {
+        let horizontal = basis.u * view_width;
+        let vertical = basis.v * view_height;
+        let lower_left_corner = basis.origin - horizontal.clone() / 2.0 - vertical.clone() / 2.0;
+        OrthographicCamera {
+            lower_left_corner,
+            horizontal,
+            vertical,
+            direction: -basis.w,
+        }
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn get_ray(&self, s: f64, t: f64, _sampler: &mut dyn Sampler) -> Option<Ray> {
+        Some(Ray::new(
+            self.lower_left_corner.clone()
+                + self.horizontal.clone() * s
+                + self.vertical.clone() * t,
+            self.direction.clone(),
+        ))
+    }
+}
@@ -0,0 +1,85 @@
+use crate::{
+    camera::{Camera, CameraBasis},
+    point::Point,
+    ray::Ray,
+    sampler::Sampler,
+    utility::PI,
+    vector3::Vector3,
+};
+
+// Sees in every direction, laid out as an equirectangular panorama: longitude across, with
+// the view direction in the middle, and latitude from straight down at the bottom to
+// straight up at the top. Made for VR viewers and environment maps, so the image is twice
+// as wide as it is tall.
+pub struct EquirectangularCamera {
+    basis: CameraBasis,
+    eye_offset: f64,
+}
+
+impl EquirectangularCamera {
+    pub fn new(basis: CameraBasis) -> EquirectangularCamera {
+        EquirectangularCamera {
+            // latitude is measured from the world's up, +y as in environment maps, so the
+            // horizon runs straight across the middle however the camera is pitched
+            basis: basis.level(&Vector3::new(0.0, 1.0, 0.0)),
+            eye_offset: 0.0,
+        }
+    }
+    // Makes this one eye of an omni-directional stereo panorama. Rays start on a circle of
+    // this radius around the camera instead of at its center, each from where an eye would
+    // be when the head turns to face that way: to the right of center for positive offsets.
+    // The circle lies flat in the levelled basis, so the eyes only ever differ sideways and
+    // never up and down.
+    pub fn with_eye_offset(mut self, eye_offset: f64) -> Self {
+        self.eye_offset = eye_offset;
+        self
+    }
+}
+
+impl Camera for EquirectangularCamera {
+    fn get_ray(&self, s: f64, t: f64, _sampler: &mut dyn Sampler) -> Option<Ray> {
+        let (sin_phi, cos_phi) = ((2.0 * s - 1.0) * PI).sin_cos();
+        let (sin_theta, cos_theta) = ((t - 0.5) * PI).sin_cos();
+        let basis = &self.basis;
+        let direction = (basis.u.clone() * sin_phi - basis.w.clone() * cos_phi) * cos_theta
+            + basis.v.clone() * sin_theta;
+        // sideways from the horizontal direction, at right angles to it and to world up
+        let eye = (basis.u.clone() * cos_phi + basis.w.clone() * sin_phi) * self.eye_offset;
+        Some(Ray::new(basis.origin.clone() + eye, direction))
+    }
+}
+
+// The six 90 degree views along the world axes from one point, as used for reflection
+// probes. The faces are laid out in a 3 by 2 grid, +x, -x, +y on top and -y, +z, -z below,
+// each looking the way the usual cube map convention has it when seen from inside the cube.
+pub struct CubemapCamera {
+    origin: Point,
+}
+
+impl CubemapCamera {
+    pub fn new(origin: Point) -> CubemapCamera {
+        CubemapCamera { origin }
+    }
+}
+
+impl Camera for CubemapCamera {
+    fn get_ray(&self, s: f64, t: f64, _sampler: &mut dyn Sampler) -> Option<Ray> {
+        let column = ((s * 3.0) as usize).min(2);
+        let row = (((1.0 - t) * 2.0).max(0.0) as usize).min(1);
+        // where on the face, from -1 to 1 rightward and upward
+        let across = s * 3.0 - column as f64;
+        let down = (1.0 - t) * 2.0 - row as f64;
+        let (a, b) = (2.0 * across - 1.0, 1.0 - 2.0 * down);
+        let axis = |x: f64, y: f64, z: f64| Vector3::new(x, y, z);
+        // (forward, right, up) of each face
+        let (forward, right, up) = match row * 3 + column {
+            0 => (axis(1., 0., 0.), axis(0., 0., -1.), axis(0., 1., 0.)),
+            1 => (axis(-1., 0., 0.), axis(0., 0., 1.), axis(0., 1., 0.)),
+            2 => (axis(0., 1., 0.), axis(1., 0., 0.), axis(0., 0., -1.)),
+            3 => (axis(0., -1., 0.), axis(1., 0., 0.), axis(0., 0., 1.)),
+            4 => (axis(0., 0., 1.), axis(1., 0., 0.), axis(0., 1., 0.)),
+            _ => (axis(0., 0., -1.), axis(-1., 0., 0.), axis(0., 1., 0.)),
+        };
+        Some(Ray::new(self.origin.clone(), forward + right * a + up * b))
+    }
+}
@@ -0,0 +1,78 @@
+use std::rc::Rc;
+
+use crate::{
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    point::Point,
+    ray::Ray,
+    vector3::Vector3,
+};
+
+// A flat parallelogram with one corner at q and sides u and v.
+pub struct Quad<T: Material + 'static> {
+    q: Point,
+    u: Vector3,
+    v: Vector3,
+    normal: Vector3,
+    d: f64,     // plane offset, so the plane is every p with dot(normal, p) = d
+    w: Vector3, // helps turn a point on the plane into coordinates along u and v
+    material: Rc<T>,
+}
+
+#[allow(dead_code)]
+impl<T: Material> Quad<T> {
+    pub fn new(q: Point, u: Vector3, v: Vector3, material: Rc<T>) -> Quad<T> {
+        let n = Vector3::cross(&u, &v);
+        let normal = Vector3::unit_vector(&n);
+        let d = Vector3::dot(&normal, &(q.clone() - Point::origin()));
+        let w = n.clone() / Vector3::dot(&n, &n);
+        Quad {
+            q,
+            u,
+            v,
+            normal,
+            d,
+            w,
+            material,
+        }
+    }
+}
+
+impl<T: Material> Hittable for Quad<T> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let denom = Vector3::dot(&self.normal, r.direction());
+        if denom.abs() < 1e-8 {
+            // ray runs parallel to the plane
+            return None;
+        }
+        let t =
+            (self.d - Vector3::dot(&self.normal, &(r.origin().clone() - Point::origin()))) / denom;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let point = r.at(t);
+        let planar_hit = point.clone() - self.q.clone();
+        let alpha = Vector3::dot(&self.w, &Vector3::cross(&planar_hit, &self.v));
+        let beta = Vector3::dot(&self.w, &Vector3::cross(&self.u, &planar_hit));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        let (normal, front_face) = HitRecord::set_face_normal(r, &self.normal);
+        let material = Rc::clone(&self.material);
+        Some(HitRecord {
+            point,
+            geometric_normal: normal.clone(),
+            normal,
+            t,
+            u: alpha,
+            v: beta,
+            dpdu: self.u.clone(),
+            dpdv: self.v.clone(),
+            front_face,
+            material,
+            object_id: 0,
+        })
+    }
+}
@@ -0,0 +1,188 @@
+use std::fmt;
+use std::str::FromStr;
+
+// A rectangle of pixels counted from the top left corner of the image, including x0 and y0
+// but not x1 and y1. Written as x,y,width,height on the command line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Window {
+    pub x0: i32,
+    pub y0: i32,
+    pub x1: i32,
+    pub y1: i32,
+}
+
+#[allow(dead_code)]
+impl Window {
+    pub fn new(x0: i32, y0: i32, x1: i32, y1: i32) -> Window {
+        Window { x0, y0, x1, y1 }
+    }
+    pub fn full(width: i32, height: i32) -> Window {
+        Window::new(0, 0, width, height)
+    }
+    pub fn width(&self) -> i32 {
+        (self.x1 - self.x0).max(0)
+    }
+    pub fn height(&self) -> i32 {
+        (self.y1 - self.y0).max(0)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.width() == 0 || self.height() == 0
+    }
+    pub fn contains(&self, x: i32, row: i32) -> bool {
+        self.x0 <= x && x < self.x1 && self.y0 <= row && row < self.y1
+    }
+
+    // the part of the window inside an image of this size
+    pub fn clamp(&self, width: i32, height: i32) -> Window {
+        Window::new(
+            self.x0.clamp(0, width),
+            self.y0.clamp(0, height),
+            self.x1.clamp(0, width),
+            self.y1.clamp(0, height),
+        )
+    }
+
+    // the window grown by margin pixels on every side, kept inside the image
+    pub fn grow(&self, margin: i32, width: i32, height: i32) -> Window {
+        Window::new(
+            self.x0 - margin,
+            self.y0 - margin,
+            self.x1 + margin,
+            self.y1 + margin,
+        )
+        .clamp(width, height)
+    }
+}
+
+impl FromStr for Window {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let numbers: Vec<i32> = s
+            .split(',')
+            .map(|n| n.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("invalid window {s}"))?;
+        match numbers[..] {
+            [x, y, width, height] if x >= 0 && y >= 0 && width > 0 && height > 0 => {
+                Ok(Window::new(x, y, x + width, y + height))
+            }
+            _ => Err(format!("invalid window {s}")),
+        }
+    }
+}
+
+impl fmt::Display for Window {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{}",
+            self.x0,
+            self.y0,
+            self.width(),
+            self.height()
+        )
+    }
+}
+
+// Part of the image given as fractions of its width and height from the top left corner,
+// like 0.25,0.25,0.75,0.75 for the middle, so it picks the same part at any image size.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub x0: f64,
+    pub y0: f64,
+    pub x1: f64,
+    pub y1: f64,
+}
+
+#[allow(dead_code)]
+impl Region {
+    // the pixels whose centers fall inside the region
+    pub fn window(&self, width: i32, height: i32) -> Window {
+        let x = |f: f64| (f * width as f64).round() as i32;
+        let y = |f: f64| (f * height as f64).round() as i32;
+        Window::new(x(self.x0), y(self.y0), x(self.x1), y(self.y1))
+    }
+}
+
+impl FromStr for Region {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let numbers: Vec<f64> = s
+            .split(',')
+            .map(|n| n.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("invalid region {s}"))?;
+        match numbers[..] {
+            [x0, y0, x1, y1]
+                if 0.0 <= x0 && x0 < x1 && x1 <= 1.0 && 0.0 <= y0 && y0 < y1 && y1 <= 1.0 =>
+            {
+                Ok(Region { x0, y0, x1, y1 })
+            }
+            _ => Err(format!("invalid region {s}")),
+        }
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{},{},{}", self.x0, self.y0, self.x1, self.y1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_parse_as_corner_and_size() {
+        assert_eq!("10,5,20,12".parse(), Ok(Window::new(10, 5, 30, 17)));
+        assert_eq!(" 0, 0, 1, 1".parse(), Ok(Window::new(0, 0, 1, 1)));
+        assert_eq!(Window::new(10, 5, 30, 17).to_string(), "10,5,20,12");
+    }
+
+    #[test]
+    fn bad_windows_are_rejected() {
+        for text in [
+            "",
+            "1,2,3",
+            "1,2,3,4,5",
+            "-1,0,4,4",
+            "0,0,0,4",
+            "0,0,4,-1",
+            "a,b,c,d",
+        ] {
+            assert!(text.parse::<Window>().is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn windows_stay_inside_the_image() {
+        let window = Window::new(50, 30, 80, 50).clamp(60, 40);
+        assert_eq!(window, Window::new(50, 30, 60, 40));
+        assert!(Window::new(70, 0, 90, 10).clamp(60, 40).is_empty());
+        let grown = Window::new(1, 1, 59, 39).grow(2, 60, 40);
+        assert_eq!(grown, Window::full(60, 40));
+        assert!(grown.contains(0, 0) && !grown.contains(60, 0));
+    }
+
+    #[test]
+    fn regions_parse_as_fractions() {
+        let region: Region = "0.25,0,0.75,0.5".parse().unwrap();
+        assert_eq!(region.window(100, 40), Window::new(25, 0, 75, 20));
+        assert_eq!(region.to_string(), "0.25,0,0.75,0.5");
+    }
+
+    #[test]
+    fn bad_regions_are_rejected() {
+        for text in [
+            "0,0,1",
+            "0.5,0,0.5,1",
+            "0.6,0,0.4,1",
+            "0,0,1.5,1",
+            "-0.1,0,1,1",
+            "x,0,1,1",
+        ] {
+            assert!(text.parse::<Region>().is_err(), "{text}");
+        }
+    }
+}
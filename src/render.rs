@@ -0,0 +1,339 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    background::Background,
+    camera::Camera,
+    checkpoint,
+    color::Color,
+    denoise::Denoiser,
+    film::Film,
+    filter::Filter,
+    hittable::{HitRecord, Hittable},
+    image::Image,
+    interrupt,
+    options::Options,
+    ray::Ray,
+    region::Window,
+    sampler::Sampler,
+    utility::INF,
+};
+
+const MAX_DEPTH: i32 = 50;
+// progressive passes double in size until they reach this many samples per pixel
+const MAX_PASS_SAMPLES: u32 = 64;
+
+// Everything needed to trace samples into a film.
+pub struct Renderer<'a, T: Hittable> {
+    world: &'a T,
+    background: &'a dyn Background,
+    camera: &'a dyn Camera,
+    filter: &'a dyn Filter,
+    options: &'a Options,
+}
+
+impl<'a, T: Hittable> Renderer<'a, T> {
+    pub fn new(
+        world: &'a T,
+        background: &'a dyn Background,
+        camera: &'a dyn Camera,
+        filter: &'a dyn Filter,
+        options: &'a Options,
+    ) -> Renderer<'a, T> {
+        Renderer {
+            world,
+            background,
+            camera,
+            filter,
+            options,
+        }
+    }
+
+    // Renders the whole image in one go, a scanline at a time. Returns false if the render
+    // was interrupted before it finished.
+    pub fn render(&self, film: &mut Film, sampler: &mut dyn Sampler) -> bool {
+        self.catch_up_aovs(film, sampler);
+        let mut last_checkpoint = Instant::now();
+        let window = self.traced_window(film);
+        for row in window.y0..window.y1 {
+            let j = film.height() - 1 - row;
+            print!("\x1B[2J\x1B[1;1H");
+            println!("Scanlines remaining: {}", window.y1 - 1 - row);
+            for i in window.x0..window.x1 {
+                self.render_pixel(film, sampler, i, j, self.options.samples);
+            }
+            if self.should_stop(film, &mut last_checkpoint) {
+                return false;
+            }
+        }
+        true
+    }
+
+    // Renders the image in passes over every pixel, each adding more samples on top of the
+    // last, and saves the image as it goes so it can be looked at while it improves.
+    // Returns false if the render was interrupted before it finished.
+    pub fn render_progressive(&self, film: &mut Film, sampler: &mut dyn Sampler) -> bool {
+        self.catch_up_aovs(film, sampler);
+        let start = Instant::now();
+        let time_limit = self.options.time_limit.map(Duration::from_secs_f64);
+        let snapshot_interval = self.options.snapshot_seconds.map(Duration::from_secs_f64);
+        let mut last_snapshot = Instant::now();
+        let mut last_checkpoint = Instant::now();
+        let window = self.traced_window(film);
+        // a resumed render picks up from where its least sampled pixel got to
+        let mut samples_done = film.min_samples().min(self.options.samples);
+        let mut pass = 0;
+
+        while samples_done < self.options.samples {
+            let pass_samples = samples_done
+                .clamp(1, MAX_PASS_SAMPLES)
+                .min(self.options.samples - samples_done);
+            for row in window.y0..window.y1 {
+                let j = film.height() - 1 - row;
+                for i in window.x0..window.x1 {
+                    self.render_pixel(film, sampler, i, j, samples_done + pass_samples);
+                }
+                if self.should_stop(film, &mut last_checkpoint) {
+                    return false;
+                }
+            }
+            samples_done += pass_samples;
+            pass += 1;
+
+            let elapsed = start.elapsed();
+            print!("\x1B[2J\x1B[1;1H");
+            println!(
+                "Pass {pass}: {samples_done} samples per pixel after {:.1}s",
+                elapsed.as_secs_f64()
+            );
+
+            let out_of_time = time_limit.is_some_and(|limit| elapsed >= limit);
+            let snapshot_due = pass % self.options.snapshot_passes.max(1) == 0
+                || snapshot_interval.is_some_and(|every| last_snapshot.elapsed() >= every);
+            if snapshot_due && !out_of_time && samples_done < self.options.samples {
+                self.write_output(film);
+                last_snapshot = Instant::now();
+            }
+            if out_of_time {
+                break;
+            }
+        }
+        true
+    }
+
+    // The pixels to trace: the render window grown by as far as the filter reaches, so the
+    // pixels at its edges get every sample they would in a render of the whole image.
+    fn traced_window(&self, film: &Film) -> Window {
+        let (width, height) = (film.width(), film.height());
+        match self.options.window(width, height) {
+            Some(window) => window.grow(self.filter.radius().ceil() as i32, width, height),
+            None => Window::full(width, height),
+        }
+    }
+
+    // Called after each scanline. Saves a checkpoint when one is due, or right away if the
+    // user pressed Ctrl-C, in which case the render should stop.
+    fn should_stop(&self, film: &Film, last_checkpoint: &mut Instant) -> bool {
+        let interrupted = interrupt::interrupted();
+        if let Some(file_name) = &self.options.checkpoint {
+            let due = last_checkpoint.elapsed().as_secs_f64() >= self.options.checkpoint_seconds;
+            if interrupted || due {
+                checkpoint::save(file_name, self.options, film).expect("Unable to save checkpoint");
+                *last_checkpoint = Instant::now();
+            }
+        }
+        interrupted
+    }
+
+    // Samples pixel (i, j), counting rows from the bottom, until it has `samples` of them.
+    // Every pixel picks up from however many samples it already has, which is what lets
+    // progressive passes and resumed renders add up to the same image. With adaptive
+    // sampling the pixel stops early once it is smooth enough. Each sample is splatted into
+    // the pixels around it as well, weighted by the reconstruction filter.
+    pub fn render_pixel(
+        &self,
+        film: &mut Film,
+        sampler: &mut dyn Sampler,
+        i: i32,
+        j: i32,
+        samples: u32,
+    ) {
+        let row = film.height() - 1 - j;
+        for s in film.pixel(i, row).samples()..samples {
+            if let Some(threshold) = self.options.noise_threshold {
+                let pixel = film.pixel(i, row);
+                if pixel.samples() >= self.options.min_samples && pixel.relative_error() < threshold
+                {
+                    break;
+                }
+            }
+            let (du, dv, ray) = self.camera_ray(film, sampler, i, j, s);
+            self.record_first_hit(film, i, row, ray.as_ref().map(|(r, _)| r));
+            let color = match ray {
+                Some((r, weight)) => self.ray_color(&r, sampler, MAX_DEPTH, None) * weight,
+                None => Color::new(0.0, 0.0, 0.0),
+            };
+            film.pixel_mut(i, row).add_sample(&color);
+            // v grows upward, film rows downward
+            film.splat(i as f64 + du, (row + 1) as f64 - dv, &color, self.filter);
+        }
+    }
+
+    // Writes the image to the output file, denoised with the help of the aovs if asked to.
+    // With a render window it is cut down to the window or pasted into an earlier image.
+    pub fn write_output(&self, film: &Film) {
+        let tone_mapping = self.options.tone_mapping();
+        let image = match film.aovs() {
+            Some(aovs) if self.options.denoise => {
+                let denoiser = Denoiser::new(self.options.denoise_strength);
+                denoiser.denoise(&film.image(), &film.variance(), aovs)
+            }
+            _ => film.image(),
+        };
+        let image = tone_mapping.develop(&image);
+        let image = match self.options.window(film.width(), film.height()) {
+            Some(window) if self.options.crop => image.crop(&window),
+            Some(window) => {
+                // the pixels traced around the window for the filter's sake are left out too
+                let mut base = match &self.options.composite {
+                    Some(file_name) => load_composite(file_name, &image),
+                    None => {
+                        let black = (0..image.pixels().len()).map(|_| Color::new(0.0, 0.0, 0.0));
+                        Image::new(image.width(), image.height(), black.collect())
+                    }
+                };
+                base.paste(&image, &window);
+                base
+            }
+            None => image,
+        };
+        image
+            .write_ppm(&self.options.output)
+            .expect("Unable to write image");
+    }
+
+    // Starts sample s of pixel (i, j) and returns where inside the pixel it landed and the
+    // camera ray through that point with its weight, if the camera sees anything there.
+    fn camera_ray(
+        &self,
+        film: &Film,
+        sampler: &mut dyn Sampler,
+        i: i32,
+        j: i32,
+        s: u32,
+    ) -> (f64, f64, Option<(Ray, f64)>) {
+        sampler.start_pixel_sample(i, j, s);
+        let (du, dv) = sampler.get_pixel_2d();
+        let u = (i as f64 + du) / (film.width() - 1) as f64;
+        let v = (j as f64 + dv) / (film.height() - 1) as f64;
+        (du, dv, self.camera.get_weighted_ray(u, v, sampler))
+    }
+
+    // Adds what a camera ray through pixel (i, row) hits first to the aov images, if the film
+    // keeps them.
+    fn record_first_hit(&self, film: &mut Film, i: i32, row: i32, r: Option<&Ray>) {
+        if let Some(aovs) = film.aovs_mut() {
+            let hit = r.and_then(|r| {
+                let rec = self.world.hit(r, 0.001, INF)?;
+                let depth = rec.t * r.direction().length();
+                Some((rec, depth))
+            });
+            aovs.add_sample(i, row, hit.as_ref().map(|(rec, depth)| (rec, *depth)));
+        }
+    }
+
+    // A resumed film has samples from before it kept aovs. Their camera rays are traced again,
+    // which gives the same rays, so the aovs cover every sample of the image.
+    fn catch_up_aovs(&self, film: &mut Film, sampler: &mut dyn Sampler) {
+        if film.aovs().is_none() {
+            return;
+        }
+        let height = film.height();
+        for j in (0..height).rev() {
+            let row = height - 1 - j;
+            for i in 0..film.width() {
+                let recorded = film.aovs().map_or(0, |aovs| aovs.samples(i, row));
+                for s in recorded..film.pixel(i, row).samples() {
+                    let (_, _, ray) = self.camera_ray(film, sampler, i, j, s);
+                    self.record_first_hit(film, i, row, ray.as_ref().map(|(r, _)| r));
+                }
+            }
+        }
+    }
+
+    // scatter_pdf is the pdf the previous bounce picked this ray's direction with, or None if
+    // it came from the camera or a mirror-like bounce that can't be sampled any other way.
+    fn ray_color(
+        &self,
+        r: &Ray,
+        sampler: &mut dyn Sampler,
+        depth: i32,
+        scatter_pdf: Option<f64>,
+    ) -> Color {
+        if depth <= 0 {
+            // If this is true then at this point we have exceeded the ray bounce limit.
+            // Since the light will never not hit the hittable object, we say no light is gathered.
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        if let Some(x) = self.world.hit(r, 0.001, INF) {
+            let direct = self.sample_background(r, &x, sampler);
+            let scatter_result = x.material.scatter(r, &x, sampler);
+            if scatter_result.success {
+                let pdf = x
+                    .material
+                    .eval(r, &x, scatter_result.scattered.direction())
+                    .map(|(_, pdf)| pdf);
+                return direct
+                    + scatter_result.attenuation
+                        * self.ray_color(&scatter_result.scattered, sampler, depth - 1, pdf);
+            }
+            return direct;
+        }
+        let color = self.background.value(r.direction());
+        match scatter_pdf {
+            // this direction could also have been found by sampling the background directly,
+            // so only count the share of it that sampling didn't already cover
+            Some(pdf) => color * power_heuristic(pdf, self.background.pdf(r.direction())),
+            None => color,
+        }
+    }
+
+    // Light reaching a diffuse hit straight from the background, found by picking a direction
+    // toward it and checking nothing is in the way.
+    fn sample_background(&self, r: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Color {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let (direction, radiance, light_pdf) = match self.background.sample(sampler) {
+            Some(sample) => sample,
+            None => return black,
+        };
+        let (brdf_cosine, scatter_pdf) = match rec.material.eval(r, rec, &direction) {
+            Some(value) if value.1 > 0.0 => value,
+            _ => return black,
+        };
+        let shadow_ray = Ray::new(rec.point.clone(), direction);
+        if self.world.hit(&shadow_ray, 0.001, INF).is_some() {
+            return black;
+        }
+        brdf_cosine * radiance * (power_heuristic(light_pdf, scatter_pdf) / light_pdf)
+    }
+}
+
+// The earlier render in file_name, to paste the window of image over.
+fn load_composite(file_name: &str, image: &Image) -> Image {
+    let base = Image::load_ppm(file_name).expect("Unable to load composite image");
+    if (base.width(), base.height()) != (image.width(), image.height()) {
+        eprintln!("{file_name} isn't the same size as the image being rendered");
+        std::process::exit(1);
+    }
+    base
+}
+
+// Weight for combining two ways of sampling the same light, favoring whichever was more
+// likely to produce the sample.
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let pdf2 = pdf * pdf;
+    let other2 = other_pdf * other_pdf;
+    if pdf2 + other2 == 0.0 {
+        return 0.0;
+    }
+    pdf2 / (pdf2 + other2)
+}
@@ -0,0 +1,64 @@
+const MULTIPLIER: u64 = 6364136223846793005;
+
+// The PCG32 random number generator. It is small, fast and completely determined by its
+// seed and stream, so the same seed always gives the same numbers on any machine.
+pub struct Pcg32 {
+    state: u64,
+    increment: u64,
+}
+
+#[allow(dead_code)]
+impl Pcg32 {
+    // Generators with the same seed but different streams give unrelated sequences.
+    pub fn new(seed: u64, stream: u64) -> Pcg32 {
+        let mut rng = Pcg32 {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(MULTIPLIER).wrapping_add(self.increment);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+    // uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        let high = (self.next_u32() as u64) << 21;
+        let low = (self.next_u32() >> 11) as u64;
+        (high | low) as f64 / (1u64 << 53) as f64
+    }
+    pub fn next_range(&mut self, min: f64, max: f64) -> f64 {
+        self.next_f64() * (max - min) + min
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the first numbers printed by pcg32-demo from pcg-random.org, seeded with 42 on stream 54
+    #[test]
+    fn matches_the_reference_sequence() {
+        let mut rng = Pcg32::new(42, 54);
+        let expected = [
+            0xa15c02b7, 0x7b47f409, 0xba1d3330, 0x83d2f293, 0xbfa4784b, 0xcbed606e,
+        ];
+        for value in expected {
+            assert_eq!(rng.next_u32(), value);
+        }
+    }
+
+    #[test]
+    fn floats_stay_below_one() {
+        let mut rng = Pcg32::new(7, 0);
+        for _ in 0..10000 {
+            let x = rng.next_f64();
+            assert!((0.0..1.0).contains(&x));
+        }
+    }
+}
@@ -0,0 +1,398 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{rng::Pcg32, utility};
+
+// first primes, one Halton base per dimension
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+// largest f64 below 1, so scaled integer samples never round up to exactly 1
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+// Source of the random numbers used for a camera ray and every bounce it takes. Each call to
+// get_1d or get_2d moves on to the next dimension of the sample, so better samplers can spread
+// the samples of a pixel evenly in each dimension instead of letting them clump.
+pub trait Sampler {
+    // Called before each camera ray with the pixel and which of its samples is being taken.
+    fn start_pixel_sample(&mut self, x: i32, y: i32, index: u32);
+    fn get_1d(&mut self) -> f64;
+    fn get_2d(&mut self) -> (f64, f64);
+
+    // Where inside the pixel the camera ray goes.
+    fn get_pixel_2d(&mut self) -> (f64, f64) {
+        self.get_2d()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SamplerKind {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+impl FromStr for SamplerKind {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "independent" => Ok(SamplerKind::Independent),
+            "stratified" => Ok(SamplerKind::Stratified),
+            "halton" => Ok(SamplerKind::Halton),
+            "sobol" => Ok(SamplerKind::Sobol),
+            _ => Err(format!("unknown sampler {s}")),
+        }
+    }
+}
+
+impl fmt::Display for SamplerKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SamplerKind::Independent => "independent",
+            SamplerKind::Stratified => "stratified",
+            SamplerKind::Halton => "halton",
+            SamplerKind::Sobol => "sobol",
+        };
+        write!(f, "{name}")
+    }
+}
+
+pub fn create_sampler(kind: SamplerKind, samples_per_pixel: u32, seed: u64) -> Box<dyn Sampler> {
+    match kind {
+        SamplerKind::Independent => Box::new(IndependentSampler::new(seed)),
+        SamplerKind::Stratified => Box::new(StratifiedSampler::new(samples_per_pixel, seed)),
+        SamplerKind::Halton => Box::new(HaltonSampler::new(seed)),
+        SamplerKind::Sobol => Box::new(SobolSampler::new(seed)),
+    }
+}
+
+// Which pixel sample a sampler is on and how many dimensions of it have been used. Every
+// number handed out depends only on the seed, the pixel, the sample index and the dimension,
+// so a render comes out the same no matter what order the pixels are visited in.
+struct SampleState {
+    seed: u64,
+    x: i32,
+    y: i32,
+    index: u32,
+    dimension: u32,
+    rng: Pcg32,
+}
+
+impl SampleState {
+    fn new(seed: u64) -> SampleState {
+        SampleState {
+            seed,
+            x: 0,
+            y: 0,
+            index: 0,
+            dimension: 0,
+            rng: Pcg32::new(seed, 0),
+        }
+    }
+    fn start(&mut self, x: i32, y: i32, index: u32) {
+        self.x = x;
+        self.y = y;
+        self.index = index;
+        self.dimension = 0;
+        // each pixel sample gets its own stream of random numbers
+        let pixel_seed = utility::hash(&[self.seed, x as u64, y as u64]);
+        self.rng = Pcg32::new(pixel_seed, index as u64);
+    }
+    // A hash of the pixel and the next dimension, then moves on to the dimension after it.
+    fn next_dimension_hash(&mut self) -> u64 {
+        let h = utility::hash(&[
+            self.seed,
+            self.x as u64,
+            self.y as u64,
+            self.dimension as u64,
+        ]);
+        self.dimension += 1;
+        h
+    }
+}
+
+// Plain uniform random numbers with no structure between samples.
+pub struct IndependentSampler {
+    state: SampleState,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> IndependentSampler {
+        IndependentSampler {
+            state: SampleState::new(seed),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, x: i32, y: i32, index: u32) {
+        self.state.start(x, y, index);
+    }
+    fn get_1d(&mut self) -> f64 {
+        self.state.rng.next_f64()
+    }
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.state.rng.next_f64(), self.state.rng.next_f64())
+    }
+}
+
+// Splits every dimension of the pixel into as many strata as there are samples and puts
+// one jittered sample in each, shuffling which stratum each sample gets per dimension.
+pub struct StratifiedSampler {
+    samples_per_pixel: u32,
+    x_strata: u32,
+    y_strata: u32,
+    state: SampleState,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> StratifiedSampler {
+        let samples_per_pixel = samples_per_pixel.max(1);
+        let x_strata = (samples_per_pixel as f64).sqrt().ceil() as u32;
+        let y_strata = samples_per_pixel.div_ceil(x_strata);
+        StratifiedSampler {
+            samples_per_pixel,
+            x_strata,
+            y_strata,
+            state: SampleState::new(seed),
+        }
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, x: i32, y: i32, index: u32) {
+        self.state.start(x, y, index);
+    }
+    fn get_1d(&mut self) -> f64 {
+        let hash = self.state.next_dimension_hash();
+        let n = self.samples_per_pixel;
+        let stratum = permutation_element(self.state.index % n, n, hash as u32);
+        ((stratum as f64 + self.state.rng.next_f64()) / n as f64).min(ONE_MINUS_EPSILON)
+    }
+    fn get_2d(&mut self) -> (f64, f64) {
+        let hash = self.state.next_dimension_hash();
+        let n = self.x_strata * self.y_strata;
+        let stratum = permutation_element(self.state.index % n, n, hash as u32);
+        let (sx, sy) = (stratum % self.x_strata, stratum / self.x_strata);
+        (
+            ((sx as f64 + self.state.rng.next_f64()) / self.x_strata as f64).min(ONE_MINUS_EPSILON),
+            ((sy as f64 + self.state.rng.next_f64()) / self.y_strata as f64).min(ONE_MINUS_EPSILON),
+        )
+    }
+}
+
+// The Halton sequence, using the radical inverse in a different prime base per dimension.
+// The digits are Owen scrambled per pixel, which keeps neighboring pixels from repeating the
+// same pattern and fixes the poor coverage large bases have with few samples.
+pub struct HaltonSampler {
+    state: SampleState,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> HaltonSampler {
+        HaltonSampler {
+            state: SampleState::new(seed),
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, x: i32, y: i32, index: u32) {
+        self.state.start(x, y, index);
+    }
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.state.dimension as usize;
+        let hash = self.state.next_dimension_hash();
+        if dimension >= PRIMES.len() {
+            // out of bases, so fall back to a hashed random number
+            let h = utility::hash(&[hash, self.state.index as u64]);
+            return (h >> 11) as f64 / (1u64 << 53) as f64;
+        }
+        scrambled_radical_inverse(PRIMES[dimension], self.state.index as u64, hash)
+    }
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+// Owen scrambled Sobol points. Each dimension (or pair of dimensions) uses the first two
+// Sobol dimensions, which are well stratified for any power of two sample count, with the
+// sample order shuffled and the bits scrambled by a per pixel, per dimension seed. The
+// shuffle keeps every power of two run of samples well stratified, so the sequence works
+// the same however many samples end up being taken.
+pub struct SobolSampler {
+    state: SampleState,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> SobolSampler {
+        SobolSampler {
+            state: SampleState::new(seed),
+        }
+    }
+    fn shuffled_index(&self, hash: u64) -> u32 {
+        owen_scramble(self.state.index, hash as u32)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, x: i32, y: i32, index: u32) {
+        self.state.start(x, y, index);
+    }
+    fn get_1d(&mut self) -> f64 {
+        let hash = self.state.next_dimension_hash();
+        let index = self.shuffled_index(hash);
+        to_unit(owen_scramble(index.reverse_bits(), (hash >> 32) as u32))
+    }
+    fn get_2d(&mut self) -> (f64, f64) {
+        let hash = self.state.next_dimension_hash();
+        let index = self.shuffled_index(hash);
+        (
+            to_unit(owen_scramble(index.reverse_bits(), (hash >> 32) as u32)),
+            to_unit(owen_scramble(
+                sobol_second_dimension(index),
+                hash as u32 ^ 0x5bd1_e995,
+            )),
+        )
+    }
+}
+
+fn scrambled_radical_inverse(base: u32, mut index: u64, seed: u64) -> f64 {
+    // mirrors the digits of index in the given base around the decimal point, randomly
+    // permuting each digit based on the digits before it
+    let base = base as u64;
+    let inverse_base = 1.0 / base as f64;
+    let mut reversed: u64 = 0;
+    let mut inverse_base_n = 1.0;
+    // keep going past the last digit of index so its trailing zeros get scrambled too
+    while 1.0 - (base - 1) as f64 * inverse_base_n < 1.0 {
+        let next = index / base;
+        let digit = index - next * base;
+        let digit_seed = utility::hash(&[seed, reversed]) as u32;
+        let scrambled = permutation_element(digit as u32, base as u32, digit_seed) as u64;
+        reversed = reversed * base + scrambled;
+        inverse_base_n *= inverse_base;
+        index = next;
+    }
+    (reversed as f64 * inverse_base_n).min(ONE_MINUS_EPSILON)
+}
+
+// Second dimension of the Sobol sequence, whose generator matrix is Pascal's triangle mod 2.
+fn sobol_second_dimension(mut index: u32) -> u32 {
+    let mut v: u32 = 1 << 31;
+    let mut result = 0;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= v;
+        }
+        index >>= 1;
+        v ^= v >> 1;
+    }
+    result
+}
+
+// Randomly flips bits of v in a way that keeps the stratification of the points intact, as
+// each bit is only ever changed based on the bits above it (Laine and Karras' hash).
+fn owen_scramble(v: u32, seed: u32) -> u32 {
+    let mut v = v.reverse_bits();
+    v ^= v.wrapping_mul(0x3d20_adea);
+    v = v.wrapping_add(seed);
+    v = v.wrapping_mul((seed >> 16) | 1);
+    v ^= v.wrapping_mul(0x0552_6c56);
+    v ^= v.wrapping_mul(0x53a2_2864);
+    v.reverse_bits()
+}
+
+// The i-th element of a random permutation of 0..length picked by seed, without building
+// the whole permutation (Kensler's method).
+fn permutation_element(mut i: u32, length: u32, seed: u32) -> u32 {
+    let mut w = length.saturating_sub(1);
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < length {
+            break;
+        }
+    }
+    (i.wrapping_add(seed)) % length
+}
+
+fn to_unit(bits: u32) -> f64 {
+    (bits as f64 / 4294967296.0).min(ONE_MINUS_EPSILON)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the first few dimensions of one sample of pixel (3, 7)
+    fn draw(sampler: &mut dyn Sampler, index: u32) -> Vec<f64> {
+        sampler.start_pixel_sample(3, 7, index);
+        let (x, y) = sampler.get_pixel_2d();
+        let (u, v) = sampler.get_2d();
+        vec![x, y, u, v, sampler.get_1d()]
+    }
+
+    #[test]
+    fn halton_and_sobol_ignore_the_sample_count() {
+        for kind in [SamplerKind::Halton, SamplerKind::Sobol] {
+            let mut few = create_sampler(kind, 16, 5);
+            let mut many = create_sampler(kind, 1024, 5);
+            for index in 0..16 {
+                assert_eq!(draw(few.as_mut(), index), draw(many.as_mut(), index));
+            }
+        }
+    }
+
+    #[test]
+    fn samples_are_the_same_in_any_order() {
+        for kind in [
+            SamplerKind::Independent,
+            SamplerKind::Stratified,
+            SamplerKind::Halton,
+            SamplerKind::Sobol,
+        ] {
+            let mut in_order = create_sampler(kind, 16, 5);
+            let first: Vec<Vec<f64>> = (0..16).map(|i| draw(in_order.as_mut(), i)).collect();
+            let mut backwards = create_sampler(kind, 16, 5);
+            for index in (0..16).rev() {
+                assert_eq!(draw(backwards.as_mut(), index), first[index as usize]);
+            }
+        }
+    }
+
+    #[test]
+    fn samples_stay_inside_the_unit_square() {
+        for kind in [SamplerKind::Halton, SamplerKind::Sobol] {
+            let mut sampler = create_sampler(kind, 64, 1);
+            for index in 0..64 {
+                for value in draw(sampler.as_mut(), index) {
+                    assert!((0.0..1.0).contains(&value));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use crate::{
+    background::Background,
+    color::Color,
+    sampler::Sampler,
+    utility::{self, PI},
+    vector3::Vector3,
+};
+
+// The sun is about half a degree across as seen from the earth
+const SUN_ANGULAR_RADIUS: f64 = 0.2665 * PI / 180.0;
+// luminance of the sun's disk above the atmosphere, in the kcd/m^2 the sky model works in
+const SUN_LUMINANCE: f64 = 1.6e6;
+// Preetham's sky is measured in kcd/m^2, this brings a midday sky down to roughly the
+// brightness of the old white to blue gradient
+const SKY_SCALE: f64 = 0.05;
+
+// Daylight from the analytic Preetham sky model with a sun disk. Turbidity describes how
+// hazy the air is, from 2 for a very clear day up to about 10 for a murky one.
+pub struct PhysicalSky {
+    sun_direction: Vector3,
+    cos_sun_radius: f64,
+    sun_radiance: Color,
+    perez_y: [f64; 5],
+    perez_x: [f64; 5],
+    perez_yy: [f64; 5],
+    // zenith luminance and chromaticity, each already divided by the Perez function at the
+    // zenith so the model only needs one evaluation per direction
+    zenith: [f64; 3],
+    intensity: f64,
+}
+
+#[allow(dead_code)]
+impl PhysicalSky {
+    // elevation is degrees above the horizon, azimuth is degrees around the vertical axis
+    // starting at +x and turning toward +z
+    pub fn new(elevation: f64, azimuth: f64, turbidity: f64, intensity: f64) -> PhysicalSky {
+        let elevation = utility::degrees_to_radians(elevation.clamp(0.0, 90.0));
+        let azimuth = utility::degrees_to_radians(azimuth);
+        let sun_direction = Vector3::new(
+            elevation.cos() * azimuth.cos(),
+            elevation.sin(),
+            elevation.cos() * azimuth.sin(),
+        );
+        let t = turbidity.clamp(1.7, 10.0);
+        let theta_s = PI / 2.0 - elevation;
+
+        let perez_y = [
+            0.1787 * t - 1.4630,
+            -0.3554 * t + 0.4275,
+            -0.0227 * t + 5.3251,
+            0.1206 * t - 2.5771,
+            -0.0670 * t + 0.3703,
+        ];
+        let perez_x = [
+            -0.0193 * t - 0.2592,
+            -0.0665 * t + 0.0008,
+            -0.0004 * t + 0.2125,
+            -0.0641 * t - 0.8989,
+            -0.0033 * t + 0.0452,
+        ];
+        let perez_yy = [
+            -0.0167 * t - 0.2608,
+            -0.0950 * t + 0.0092,
+            -0.0079 * t + 0.2102,
+            -0.0441 * t - 1.6537,
+            -0.0109 * t + 0.0529,
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_y = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let (t2, s, s2, s3) = (t * t, theta_s, theta_s * theta_s, theta_s.powi(3));
+        let zenith_x = t2 * (0.00166 * s3 - 0.00375 * s2 + 0.00209 * s)
+            + t * (-0.02903 * s3 + 0.06377 * s2 - 0.03202 * s + 0.00394)
+            + (0.11693 * s3 - 0.21196 * s2 + 0.06052 * s + 0.25886);
+        let zenith_yy = t2 * (0.00275 * s3 - 0.00610 * s2 + 0.00317 * s)
+            + t * (-0.04214 * s3 + 0.08970 * s2 - 0.04153 * s + 0.00516)
+            + (0.15346 * s3 - 0.26756 * s2 + 0.06670 * s + 0.26688);
+
+        let zenith = [
+            zenith_y.max(0.0) / perez(&perez_y, 1.0, theta_s.cos()),
+            zenith_x / perez(&perez_x, 1.0, theta_s.cos()),
+            zenith_yy / perez(&perez_yy, 1.0, theta_s.cos()),
+        ];
+
+        PhysicalSky {
+            sun_radiance: sun_radiance(theta_s, t),
+            sun_direction,
+            cos_sun_radius: SUN_ANGULAR_RADIUS.cos(),
+            perez_y,
+            perez_x,
+            perez_yy,
+            zenith,
+            intensity,
+        }
+    }
+
+    fn sky(&self, direction: &Vector3) -> Color {
+        // below the horizon just continue the color at the horizon
+        let cos_theta = direction.y().max(0.01);
+        let cos_gamma = Vector3::dot(direction, &self.sun_direction);
+        let luminance = self.zenith[0] * perez(&self.perez_y, cos_theta, cos_gamma);
+        let x = self.zenith[1] * perez(&self.perez_x, cos_theta, cos_gamma);
+        let y = self.zenith[2] * perez(&self.perez_yy, cos_theta, cos_gamma);
+        xyy_to_rgb(x, y, luminance) * SKY_SCALE
+    }
+}
+
+impl Background for PhysicalSky {
+    fn value(&self, direction: &Vector3) -> Color {
+        let direction = Vector3::unit_vector(direction);
+        let mut color = self.sky(&direction);
+        if Vector3::dot(&direction, &self.sun_direction) >= self.cos_sun_radius {
+            color += self.sun_radiance.clone() * SKY_SCALE;
+        }
+        color * self.intensity
+    }
+
+    // Only the sun is sampled directly, the rest of the sky is dim and smooth enough to be
+    // found by ordinary bounces.
+    fn sample(&self, sampler: &mut dyn Sampler) -> Option<(Vector3, Color, f64)> {
+        if self.sun_direction.y() <= 0.0 {
+            return None;
+        }
+        // pick a direction uniformly inside the cone the sun covers
+        let (u1, u2) = sampler.get_2d();
+        let cos_theta = 1.0 - u1 * (1.0 - self.cos_sun_radius);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u2;
+        let (u, v) = Vector3::orthonormal_basis(&self.sun_direction);
+        let direction = u * (sin_theta * phi.cos())
+            + v * (sin_theta * phi.sin())
+            + self.sun_direction.clone() * cos_theta;
+        let pdf = self.pdf(&direction);
+        Some((direction.clone(), self.value(&direction), pdf))
+    }
+
+    fn pdf(&self, direction: &Vector3) -> f64 {
+        // a sun below the horizon is never sampled, so bounces have to find it on their own
+        if self.sun_direction.y() <= 0.0 {
+            return 0.0;
+        }
+        let cosine = Vector3::dot(&Vector3::unit_vector(direction), &self.sun_direction);
+        if cosine < self.cos_sun_radius {
+            return 0.0;
+        }
+        1.0 / (2.0 * PI * (1.0 - self.cos_sun_radius))
+    }
+}
+
+// Perez's formula for the relative brightness of the sky at angle theta from the zenith
+// and gamma from the sun.
+fn perez(coefficients: &[f64; 5], cos_theta: f64, cos_gamma: f64) -> f64 {
+    let [a, b, c, d, e] = *coefficients;
+    let gamma = cos_gamma.clamp(-1.0, 1.0).acos();
+    (1.0 + a * (b / cos_theta).exp()) * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+}
+
+// Color of the sun after passing through the atmosphere, where it loses more blue the
+// lower it sits and the hazier the air is.
+fn sun_radiance(theta_s: f64, turbidity: f64) -> Color {
+    let degrees = theta_s.to_degrees();
+    let air_mass = 1.0 / (theta_s.cos() + 0.15 * (93.885 - degrees).max(0.001).powf(-1.253));
+    let beta = 0.04608 * turbidity - 0.04586;
+    let transmittance = |wavelength: f64| {
+        // wavelength in micrometers, rayleigh scattering then aerosols
+        let rayleigh = (-0.008735 * wavelength.powf(-4.08) * air_mass).exp();
+        let aerosol = (-beta * wavelength.powf(-1.3) * air_mass).exp();
+        rayleigh * aerosol
+    };
+    Color::new(
+        transmittance(0.65),
+        transmittance(0.57),
+        transmittance(0.475),
+    ) * SUN_LUMINANCE
+}
+
+fn xyy_to_rgb(x: f64, y: f64, luminance: f64) -> Color {
+    if y <= 0.0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    let cx = x / y * luminance;
+    let cz = (1.0 - x - y) / y * luminance;
+    Color::new(
+        (3.2406 * cx - 1.5372 * luminance - 0.4986 * cz).max(0.0),
+        (-0.9689 * cx + 1.8758 * luminance + 0.0415 * cz).max(0.0),
+        (0.0557 * cx - 0.2040 * luminance + 1.0570 * cz).max(0.0),
+    )
+}
@@ -5,6 +5,7 @@ use crate::{
     material::Material,
     point::Point,
     ray::Ray,
+    utility::PI,
     vector3::Vector3,
 };
 
@@ -23,6 +24,22 @@ impl<T: Material> Sphere<T> {
             material,
         }
     }
+
+    // Maps a point on the unit sphere to texture coordinates, where u goes around the y axis
+    // starting from -x and v goes from the bottom pole to the top one. Also returns how the
+    // surface point moves with u and v for a sphere of the given radius.
+    fn get_sphere_uv(p: &Vector3, radius: f64) -> ((f64, f64), (Vector3, Vector3)) {
+        let theta = (-p.y()).clamp(-1.0, 1.0).acos();
+        let phi = (-p.z()).atan2(p.x()) + PI;
+
+        let dpdu = Vector3::new(p.z(), 0.0, -p.x()) * (2.0 * PI * radius);
+        let dpdv = Vector3::new(
+            -phi.cos() * theta.cos(),
+            theta.sin(),
+            phi.sin() * theta.cos(),
+        ) * (PI * radius);
+        ((phi / (2.0 * PI), theta / PI), (dpdu, dpdv))
+    }
 }
 
 impl<T: Material> Hittable for Sphere<T> {
@@ -51,14 +68,21 @@ impl<T: Material> Hittable for Sphere<T> {
         let point = r.at(t);
         let outward_normal = (point.clone() - self.center.clone()) / self.radius;
         let (normal, front_face) = HitRecord::set_face_normal(r, &outward_normal);
+        let ((u, v), (dpdu, dpdv)) = Sphere::<T>::get_sphere_uv(&outward_normal, self.radius);
         let material = Rc::clone(&self.material);
         let hit_record = HitRecord {
             point,
+            geometric_normal: normal.clone(),
             normal,
             t,
+            u,
+            v,
+            dpdu,
+            dpdv,
             front_face,
             material,
+            object_id: 0,
         };
-        return Some(hit_record);
+        Some(hit_record)
     }
 }
@@ -0,0 +1,77 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{camera::Camera, ray::Ray, sampler::Sampler};
+
+// How the two eyes share one image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StereoLayout {
+    // left eye on the left half, right eye on the right
+    SideBySide,
+    // left eye on the top half, right eye on the bottom
+    TopBottom,
+}
+
+impl FromStr for StereoLayout {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "side-by-side" => Ok(StereoLayout::SideBySide),
+            "top-bottom" => Ok(StereoLayout::TopBottom),
+            _ => Err(format!("unknown stereo layout {s}")),
+        }
+    }
+}
+
+impl fmt::Display for StereoLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            StereoLayout::SideBySide => "side-by-side",
+            StereoLayout::TopBottom => "top-bottom",
+        };
+        write!(f, "{name}")
+    }
+}
+
+// Renders the views of both eyes into one image for VR headsets and 3D displays, each half
+// going to its own camera.
+pub struct StereoCamera {
+    left: Box<dyn Camera>,
+    right: Box<dyn Camera>,
+    layout: StereoLayout,
+}
+
+impl StereoCamera {
+    pub fn new(
+        left: Box<dyn Camera>,
+        right: Box<dyn Camera>,
+        layout: StereoLayout,
+    ) -> StereoCamera {
+        StereoCamera {
+            left,
+            right,
+            layout,
+        }
+    }
+
+    // the eye that sees (s, t) and where that is on its own image
+    fn eye(&self, s: f64, t: f64) -> (&dyn Camera, f64, f64) {
+        match self.layout {
+            StereoLayout::SideBySide if s < 0.5 => (self.left.as_ref(), 2.0 * s, t),
+            StereoLayout::SideBySide => (self.right.as_ref(), 2.0 * s - 1.0, t),
+            StereoLayout::TopBottom if t >= 0.5 => (self.left.as_ref(), s, 2.0 * t - 1.0),
+            StereoLayout::TopBottom => (self.right.as_ref(), s, 2.0 * t),
+        }
+    }
+}
+
+impl Camera for StereoCamera {
+    fn get_ray(&self, s: f64, t: f64, sampler: &mut dyn Sampler) -> Option<Ray> {
+        let (eye, s, t) = self.eye(s, t);
+        eye.get_ray(s, t, sampler)
+    }
+    fn get_weighted_ray(&self, s: f64, t: f64, sampler: &mut dyn Sampler) -> Option<(Ray, f64)> {
+        let (eye, s, t) = self.eye(s, t);
+        eye.get_weighted_ray(s, t, sampler)
+    }
+}
@@ -0,0 +1,49 @@
+use crate::{color::Color, image::Image, point::Point};
+
+pub trait Texture {
+    fn value(&self, u: f64, v: f64, p: &Point) -> Color;
+}
+
+pub struct SolidColor {
+    color: Color,
+}
+
+#[allow(dead_code)]
+impl SolidColor {
+    pub fn new(color: Color) -> SolidColor {
+        SolidColor { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Point) -> Color {
+        self.color.clone()
+    }
+}
+
+pub struct ImageTexture {
+    image: Image,
+}
+
+#[allow(dead_code)]
+impl ImageTexture {
+    pub fn new(image: Image) -> ImageTexture {
+        ImageTexture { image }
+    }
+    pub fn load(file_name: &str) -> std::io::Result<ImageTexture> {
+        Ok(ImageTexture::new(Image::load_ppm(file_name)?))
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Point) -> Color {
+        // texture coordinates wrap around so images can tile, and v = 0 is the bottom row
+        let width = self.image.width();
+        let height = self.image.height();
+        let x = (u.rem_euclid(1.0) * width as f64) as usize;
+        let y = ((1.0 - v.rem_euclid(1.0)) * height as f64) as usize;
+        self.image
+            .pixel(x.min(width - 1), y.min(height - 1))
+            .clone()
+    }
+}
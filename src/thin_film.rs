@@ -0,0 +1,70 @@
+use crate::{color::Color, utility::PI};
+
+// wavelength bands in nanometers that get averaged into the red, green and blue channels
+const BANDS: [(f64, f64); 3] = [(600.0, 700.0), (500.0, 600.0), (400.0, 500.0)];
+const SAMPLES_PER_BAND: i32 = 8;
+
+// A thin transparent film, like a soap bubble wall or an oil slick, sitting on top of a
+// surface. Light bouncing off the top and bottom of the film interferes, so how much gets
+// reflected depends on wavelength, giving colors that shift with the viewing angle.
+pub struct ThinFilm {
+    thickness: f64, // in nanometers
+    ir: f64,
+}
+
+#[allow(dead_code)]
+impl ThinFilm {
+    pub fn new(thickness: f64, ir: f64) -> ThinFilm {
+        ThinFilm { thickness, ir }
+    }
+    pub fn clone(&self) -> ThinFilm {
+        ThinFilm {
+            thickness: self.thickness,
+            ir: self.ir,
+        }
+    }
+
+    // Reflectance of each color channel for light arriving at cos_theta to the normal from
+    // a medium of index outside_ir, where the film sits on a substrate of index substrate_ir.
+    pub fn reflectance(&self, cos_theta: f64, outside_ir: f64, substrate_ir: [f64; 3]) -> Color {
+        let mut channels = [0.0; 3];
+        for (channel, (low, high)) in BANDS.iter().enumerate() {
+            let mut total = 0.0;
+            for i in 0..SAMPLES_PER_BAND {
+                let wavelength = low + (high - low) * (i as f64 + 0.5) / SAMPLES_PER_BAND as f64;
+                total +=
+                    self.airy_reflectance(cos_theta, outside_ir, substrate_ir[channel], wavelength);
+            }
+            channels[channel] = total / SAMPLES_PER_BAND as f64;
+        }
+        Color::new(channels[0], channels[1], channels[2])
+    }
+
+    // Unpolarized reflectance of the film at a single wavelength, summing every bounce
+    // inside the film (the Airy formula), averaged over s and p polarization.
+    fn airy_reflectance(&self, cos1: f64, n1: f64, n3: f64, wavelength: f64) -> f64 {
+        let n2 = self.ir;
+        let sin1 = (1.0 - cos1 * cos1).max(0.0).sqrt();
+        let sin2 = n1 * sin1 / n2;
+        let sin3 = n1 * sin1 / n3;
+        if sin2 >= 1.0 || sin3 >= 1.0 {
+            // total internal reflection somewhere in the stack, nothing gets through
+            return 1.0;
+        }
+        let cos2 = (1.0 - sin2 * sin2).sqrt();
+        let cos3 = (1.0 - sin3 * sin3).sqrt();
+
+        let phase = 4.0 * PI * n2 * self.thickness * cos2 / wavelength;
+
+        let rs12 = (n1 * cos1 - n2 * cos2) / (n1 * cos1 + n2 * cos2);
+        let rp12 = (n2 * cos1 - n1 * cos2) / (n2 * cos1 + n1 * cos2);
+        let rs23 = (n2 * cos2 - n3 * cos3) / (n2 * cos2 + n3 * cos3);
+        let rp23 = (n3 * cos2 - n2 * cos3) / (n3 * cos2 + n2 * cos3);
+
+        let airy = |r12: f64, r23: f64| {
+            let cross = 2.0 * r12 * r23 * phase.cos();
+            (r12 * r12 + r23 * r23 + cross) / (1.0 + r12 * r12 * r23 * r23 + cross)
+        };
+        0.5 * (airy(rs12, rs23) + airy(rp12, rp23))
+    }
+}
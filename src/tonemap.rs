@@ -0,0 +1,238 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{color::Color, image::Image};
+
+// Squeezes the unbounded brightness of the rendered image into the 0 to 1 a picture file
+// can show.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMapper {
+    // cut off anything brighter than white
+    Clamp,
+    // L / (1 + L) on the luminance, which rolls highlights off but never reaches white
+    Reinhard,
+    // Reinhard scaled so the white point comes out exactly white
+    ExtendedReinhard,
+    // John Hable's filmic curve from Uncharted 2
+    Hable,
+    // Krzysztof Narkowicz's fit of the ACES filmic curve
+    Aces,
+}
+
+impl FromStr for ToneMapper {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clamp" => Ok(ToneMapper::Clamp),
+            "reinhard" => Ok(ToneMapper::Reinhard),
+            "extended-reinhard" => Ok(ToneMapper::ExtendedReinhard),
+            "hable" => Ok(ToneMapper::Hable),
+            "aces" => Ok(ToneMapper::Aces),
+            _ => Err(format!("unknown tone mapper {s}")),
+        }
+    }
+}
+
+impl fmt::Display for ToneMapper {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ToneMapper::Clamp => "clamp",
+            ToneMapper::Reinhard => "reinhard",
+            ToneMapper::ExtendedReinhard => "extended-reinhard",
+            ToneMapper::Hable => "hable",
+            ToneMapper::Aces => "aces",
+        };
+        write!(f, "{name}")
+    }
+}
+
+// How the linear colors of the film become the colors written to an image file: an
+// exposure adjustment, then a tone mapper, then a transfer function to encode the result.
+// None of this changes the film itself, so every image written can be developed its own way.
+pub struct ToneMapping {
+    scale: f64,
+    mapper: ToneMapper,
+    white: Option<f64>,
+    srgb: bool,
+}
+
+#[allow(dead_code)]
+impl ToneMapping {
+    // exposure is in stops, each one doubling the brightness
+    pub fn new(exposure: f64, mapper: ToneMapper) -> ToneMapping {
+        ToneMapping {
+            scale: exposure.exp2(),
+            mapper,
+            white: None,
+            srgb: false,
+        }
+    }
+    // The luminance, after exposure, that extended Reinhard maps to white. Without one the
+    // brightest pixel of the image is used.
+    pub fn with_white(mut self, white: f64) -> Self {
+        self.white = Some(white);
+        self
+    }
+    // encode with the sRGB curve instead of the plain gamma 2 of the original renderer
+    pub fn with_srgb(mut self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+
+    // Turns a linear image into one ready to be written to a file.
+    pub fn develop(&self, image: &Image) -> Image {
+        let tone_mapping = self.for_image(image.pixels());
+        let pixels = image
+            .pixels()
+            .iter()
+            .map(|color| tone_mapping.apply(color))
+            .collect();
+        Image::new(image.width(), image.height(), pixels)
+    }
+
+    // Settles anything that depends on the whole image, which is the white point.
+    fn for_image(&self, colors: &[Color]) -> ToneMapping {
+        let white = self.white.unwrap_or_else(|| {
+            colors
+                .iter()
+                .map(|color| color.luminance() * self.scale)
+                .fold(0.0, f64::max)
+        });
+        ToneMapping {
+            scale: self.scale,
+            mapper: self.mapper,
+            white: Some(white),
+            srgb: self.srgb,
+        }
+    }
+
+    // Maps a linear color to encoded values between 0 and 1.
+    pub fn apply(&self, color: &Color) -> Color {
+        let color = color.clone() * self.scale;
+        let mapped = match self.mapper {
+            ToneMapper::Clamp => color,
+            ToneMapper::Reinhard => {
+                let luminance = color.luminance();
+                color * (1.0 / (1.0 + luminance.max(0.0)))
+            }
+            ToneMapper::ExtendedReinhard => {
+                let luminance = color.luminance().max(0.0);
+                let white = self.white.unwrap_or(1.0).max(1e-6);
+                let mapped = luminance * (1.0 + luminance / (white * white)) / (1.0 + luminance);
+                if luminance > 0.0 {
+                    color * (mapped / luminance)
+                } else {
+                    color
+                }
+            }
+            ToneMapper::Hable => {
+                // the curve is made for a linear white of 11.2, after doubling the exposure
+                const WHITE: f64 = 11.2;
+                let scale = 1.0 / hable(WHITE);
+                map_channels(&color, |c| hable(2.0 * c) * scale)
+            }
+            ToneMapper::Aces => {
+                // the fit expects its input scaled by 0.6
+                map_channels(&color, |c| {
+                    let c = 0.6 * c;
+                    c * (2.51 * c + 0.03) / (c * (2.43 * c + 0.59) + 0.14)
+                })
+            }
+        };
+        map_channels(&mapped, |c| {
+            let c = c.clamp(0.0, 1.0);
+            if self.srgb {
+                srgb_encode(c)
+            } else {
+                c.sqrt()
+            }
+        })
+    }
+}
+
+fn map_channels<F: Fn(f64) -> f64>(color: &Color, f: F) -> Color {
+    Color::new(f(color.red()), f(color.green()), f(color.blue()))
+}
+
+fn hable(x: f64) -> f64 {
+    const A: f64 = 0.15;
+    const B: f64 = 0.50;
+    const C: f64 = 0.10;
+    const D: f64 = 0.20;
+    const E: f64 = 0.02;
+    const F: f64 = 0.30;
+    (x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F) - E / F
+}
+
+fn srgb_encode(c: f64) -> f64 {
+    if c <= 0.0031308 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the tone mapped value of a gray, with the gamma 2 encoding undone
+    fn mapped(tone_mapping: &ToneMapping, value: f64) -> f64 {
+        let encoded = tone_mapping.apply(&Color::new(value, value, value)).red();
+        encoded * encoded
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{a} != {b}");
+    }
+
+    #[test]
+    fn reinhard_halves_a_luminance_of_one() {
+        let reinhard = ToneMapping::new(0.0, ToneMapper::Reinhard);
+        assert_close(mapped(&reinhard, 1.0), 0.5);
+        assert_close(mapped(&reinhard, 3.0), 0.75);
+        // each stop of exposure doubles the input
+        let brighter = ToneMapping::new(1.0, ToneMapper::Reinhard);
+        assert_close(mapped(&brighter, 1.5), 0.75);
+    }
+
+    #[test]
+    fn extended_reinhard_maps_the_white_point_to_white() {
+        let extended = ToneMapping::new(0.0, ToneMapper::ExtendedReinhard).with_white(4.0);
+        assert_close(mapped(&extended, 4.0), 1.0);
+        assert_close(mapped(&extended, 1.0), 0.53125);
+        // without a white point the brightest color of the image becomes white
+        let colors = [Color::new(2.0, 2.0, 2.0), Color::new(0.5, 0.5, 0.5)];
+        let for_image = ToneMapping::new(0.0, ToneMapper::ExtendedReinhard).for_image(&colors);
+        assert_close(mapped(&for_image, 2.0), 1.0);
+    }
+
+    #[test]
+    fn hable_maps_its_linear_white_to_white() {
+        let hable = ToneMapping::new(0.0, ToneMapper::Hable);
+        // the exposure bias of 2 puts 5.6 on the curve's white of 11.2
+        assert_close(mapped(&hable, 5.6), 1.0);
+        assert_close(mapped(&hable, 0.18), 0.128338448855627);
+        assert_close(mapped(&hable, 0.0), 0.0);
+    }
+
+    #[test]
+    fn aces_matches_the_fitted_curve() {
+        let aces = ToneMapping::new(0.0, ToneMapper::Aces);
+        assert_close(mapped(&aces, 1.0 / 0.6), 2.54 / 3.16);
+        assert_close(mapped(&aces, 1.0), 0.673290473407364);
+        assert_close(mapped(&aces, 0.0), 0.0);
+    }
+
+    #[test]
+    fn srgb_is_continuous_at_the_knee() {
+        assert_close(srgb_encode(0.0031308), 12.92 * 0.0031308);
+        assert!((srgb_encode(0.0031308 + 1e-12) - srgb_encode(0.0031308)).abs() < 1e-6);
+        assert_close(srgb_encode(1.0), 1.0);
+        let srgb = ToneMapping::new(0.0, ToneMapper::Clamp).with_srgb(true);
+        let encoded = srgb.apply(&Color::new(0.0031308, 0.5, 2.0));
+        assert_close(encoded.red(), 0.040449936);
+        assert_close(encoded.green(), 1.055 * 0.5f64.powf(1.0 / 2.4) - 0.055);
+        assert_close(encoded.blue(), 1.0);
+    }
+}
@@ -0,0 +1,82 @@
+use crate::{
+    hittable::{HitRecord, Hittable},
+    point::Point,
+    ray::Ray,
+    vector3::Vector3,
+};
+
+// An object scaled evenly and turned about the vertical axis around a pivot point, then
+// moved, without touching the object itself. Rays are carried into the object's own space
+// instead, so an animation can pose the same object differently every frame.
+pub struct Transformed {
+    object: Box<dyn Hittable>,
+    translation: Vector3,
+    pivot: Vector3,
+    sin_theta: f64,
+    cos_theta: f64,
+    scale: f64,
+}
+
+#[allow(dead_code)]
+impl Transformed {
+    // rotation is in degrees, counterclockwise seen from above
+    pub fn new(
+        object: Box<dyn Hittable>,
+        translation: Vector3,
+        rotation: f64,
+        scale: f64,
+    ) -> Transformed {
+        let theta = rotation.to_radians();
+        Transformed {
+            object,
+            translation,
+            pivot: Vector3::new(0.0, 0.0, 0.0),
+            sin_theta: theta.sin(),
+            cos_theta: theta.cos(),
+            scale,
+        }
+    }
+
+    // the point turning and scaling happen around, the world origin by default
+    pub fn with_pivot(mut self, pivot: Point) -> Self {
+        self.pivot = pivot - Point::origin();
+        self
+    }
+
+    fn rotate(&self, v: &Vector3, sin_theta: f64) -> Vector3 {
+        Vector3::new(
+            self.cos_theta * v.x() + sin_theta * v.z(),
+            v.y(),
+            -sin_theta * v.x() + self.cos_theta * v.z(),
+        )
+    }
+    fn to_world(&self, v: &Vector3) -> Vector3 {
+        self.rotate(v, self.sin_theta)
+    }
+    fn to_object(&self, v: &Vector3) -> Vector3 {
+        self.rotate(v, -self.sin_theta)
+    }
+}
+
+impl Hittable for Transformed {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        // the direction shrinks along with the origin, so t means the same in both spaces
+        let origin = r.origin().clone() - Point::origin() - self.translation.clone();
+        let origin =
+            self.to_object(&(origin - self.pivot.clone())) / self.scale + self.pivot.clone();
+        let direction = self.to_object(r.direction()) / self.scale;
+        let mut rec =
+            self.object
+                .hit(&Ray::new(Point::origin() + origin, direction), t_min, t_max)?;
+
+        let point = rec.point.clone() - Point::origin() - self.pivot.clone();
+        let point =
+            self.to_world(&point) * self.scale + self.pivot.clone() + self.translation.clone();
+        rec.point = Point::origin() + point;
+        rec.normal = self.to_world(&rec.normal);
+        rec.geometric_normal = self.to_world(&rec.geometric_normal);
+        rec.dpdu = self.to_world(&rec.dpdu) * self.scale;
+        rec.dpdv = self.to_world(&rec.dpdv) * self.scale;
+        Some(rec)
+    }
+}
@@ -16,13 +16,20 @@ pub fn degrees_to_radians(deg: f64) -> f64 {
     deg * PI / 180.0
 }
 
-pub fn clamp(val: f64, min: f64, max: f64) -> f64 {
-    // this function ensures that the passed in val is between the min and max params
-    if val > max {
-        return max;
+pub fn hash(values: &[u64]) -> u64 {
+    // mixes the bits of the values into a well scrambled number that is the same every time
+    let mut h: u64 = 0x9e37_79b9_7f4a_7c15;
+    for value in values {
+        h ^= value;
+        h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        h ^= h >> 31;
     }
-    if val < min {
-        return min;
-    }
-    val
+    h
+}
+
+pub fn hash_to_unit(values: &[f64]) -> f64 {
+    // like hash but for floats and giving a number in [0, 1)
+    let bits: Vec<u64> = values.iter().map(|v| v.to_bits()).collect();
+    (hash(&bits) >> 11) as f64 / (1u64 << 53) as f64
 }
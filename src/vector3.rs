@@ -1,4 +1,5 @@
-use crate::utility::{rand, rand_range};
+use crate::rng::Pcg32;
+use crate::utility::{rand, rand_range, PI};
 use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub};
 
 #[derive(Debug)]
@@ -45,6 +46,13 @@ impl Vector3 {
             z: rand_range(min, max),
         }
     }
+    pub fn new_random_from(rng: &mut Pcg32, min: f64, max: f64) -> Vector3 {
+        Vector3 {
+            x: rng.next_range(min, max),
+            y: rng.next_range(min, max),
+            z: rng.next_range(min, max),
+        }
+    }
     pub fn random_in_unit_sphere() -> Vector3 {
         loop {
             let v = Vector3::new_random_range(-1.0, 1.0);
@@ -74,6 +82,31 @@ impl Vector3 {
         }
     }
 
+    // The sample_ functions map uniform numbers in [0, 1) from a sampler onto the same shapes
+    // as the random_ ones, so well spread out samples stay well spread out.
+    pub fn sample_unit_vector((u1, u2): (f64, f64)) -> Vector3 {
+        let z = 1.0 - 2.0 * u1;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * u2;
+        Vector3::new(r * phi.cos(), r * phi.sin(), z)
+    }
+    pub fn sample_in_unit_sphere(u: (f64, f64), radius_sample: f64) -> Vector3 {
+        Vector3::sample_unit_vector(u) * radius_sample.cbrt()
+    }
+    pub fn sample_in_unit_disk((u1, u2): (f64, f64)) -> Vector3 {
+        // concentric mapping from the square to the disk, which keeps neighboring samples close
+        let (ox, oy) = (2.0 * u1 - 1.0, 2.0 * u2 - 1.0);
+        if ox == 0.0 && oy == 0.0 {
+            return Vector3::new(0.0, 0.0, 0.0);
+        }
+        let (r, theta) = if ox.abs() > oy.abs() {
+            (ox, PI / 4.0 * (oy / ox))
+        } else {
+            (oy, PI / 2.0 - PI / 4.0 * (ox / oy))
+        };
+        Vector3::new(r * theta.cos(), r * theta.sin(), 0.0)
+    }
+
     pub fn length(&self) -> f64 {
         self.length_squared().sqrt()
     }
@@ -100,6 +133,18 @@ impl Vector3 {
         copy / length
     }
 
+    pub fn orthonormal_basis(w: &Vector3) -> (Vector3, Vector3) {
+        // two unit vectors perpendicular to the unit vector w and to each other
+        let axis = if w.x.abs() > 0.9 {
+            Vector3::new(0.0, 1.0, 0.0)
+        } else {
+            Vector3::new(1.0, 0.0, 0.0)
+        };
+        let v = Vector3::unit_vector(&Vector3::cross(w, &axis));
+        let u = Vector3::cross(&v, w);
+        (u, v)
+    }
+
     pub fn near_zero(&self) -> bool {
         // Returns true if this vector is close to 0 in all its dimensions
         let delta = 1e-8;